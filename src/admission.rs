@@ -0,0 +1,122 @@
+/// Decides whether [`Cache::insert`](crate::Cache::insert) lets a new key into
+/// a full cache.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AdmissionPolicy {
+    /// Always admit the new key, evicting the least recently used entry.
+    #[default]
+    Always,
+    /// Only admit the new key if it is estimated to have been accessed more
+    /// often than the entry it would evict, as in TinyLFU. Otherwise the pair
+    /// is handed back and the cache is left untouched.
+    ///
+    /// The estimates come from a count-min sketch of four rows of one-byte
+    /// counters, each row as wide as the capacity rounded up to a power of
+    /// two, with at least 16 counters, which makes about four bytes per entry,
+    /// up to 4 MiB. Every insertion and every lookup through a `&mut self`
+    /// method, be it a hit or a miss, counts as an access, and every ten times
+    /// the capacity accesses all counters are halved, so that old popularity
+    /// fades. Lookups through `&self`, such as `peek` and `contains_key`,
+    /// cannot update the sketch and are not counted.
+    TinyLfu,
+}
+
+const DEPTH: usize = 4;
+
+/// A count-min sketch, estimating how often each hash was recorded.
+#[derive(Debug, Clone)]
+pub(crate) struct FrequencySketch {
+    counters: Box<[u8]>,
+    width: usize,
+    samples: usize,
+    reset_at: usize,
+}
+
+impl FrequencySketch {
+    pub(crate) fn new(capacity: usize) -> Self {
+        let width = capacity.clamp(16, 1 << 20).next_power_of_two();
+
+        Self {
+            counters: vec![0; width * DEPTH].into_boxed_slice(),
+            width,
+            samples: 0,
+            reset_at: capacity.saturating_mul(10).max(1),
+        }
+    }
+
+    pub(crate) fn increment(&mut self, hash: u64) {
+        for index in self.indices(hash) {
+            self.counters[index] = self.counters[index].saturating_add(1);
+        }
+
+        self.samples += 1;
+
+        if self.samples >= self.reset_at {
+            self.reset();
+        }
+    }
+
+    pub(crate) fn estimate(&self, hash: u64) -> u8 {
+        self.indices(hash)
+            .map(|index| self.counters[index])
+            .min()
+            .unwrap_or(0)
+    }
+
+    fn reset(&mut self) {
+        for counter in self.counters.iter_mut() {
+            *counter /= 2;
+        }
+
+        self.samples = 0;
+    }
+
+    // Derives one counter per row from the two halves of the hash.
+    fn indices(&self, hash: u64) -> impl Iterator<Item = usize> {
+        let low = hash as usize;
+        let high = (hash >> 32) as usize | 1;
+        let width = self.width;
+
+        (0..DEPTH)
+            .map(move |row| row * width + (low.wrapping_add(row.wrapping_mul(high)) & (width - 1)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frequency_sketch() {
+        let mut sketch = FrequencySketch::new(16);
+
+        for _ in 0..5 {
+            sketch.increment(42);
+        }
+
+        sketch.increment(7);
+        assert!(sketch.estimate(42) >= 5);
+        assert!(sketch.estimate(7) >= 1);
+        assert!(sketch.estimate(42) > sketch.estimate(7));
+    }
+
+    #[test]
+    fn test_frequency_sketch_reset() {
+        let mut sketch = FrequencySketch::new(1);
+
+        for _ in 0..9 {
+            sketch.increment(42);
+        }
+
+        assert_eq!(sketch.estimate(42), 9);
+        sketch.increment(42);
+        assert_eq!(sketch.estimate(42), 5);
+
+        for _ in 0..9 {
+            sketch.increment(42);
+        }
+
+        assert_eq!(sketch.estimate(42), 14);
+        sketch.increment(42);
+        assert_eq!(sketch.estimate(42), 7);
+    }
+}
@@ -0,0 +1,384 @@
+use std::fmt;
+use std::mem::MaybeUninit;
+
+const NIL: usize = usize::MAX;
+
+struct Slot<K, V> {
+    key: K,
+    value: V,
+    prev: usize,
+    next: usize,
+}
+
+/// An LRU cache of at most `N` entries stored inline, without any heap
+/// allocation.
+///
+/// The entries live in an array and are linked by their indices instead of by
+/// pointers, and keys are found by comparing them one by one, so this is only
+/// worth it over [`Cache`](crate::Cache) for small `N`.
+pub struct ArrayCache<K, V, const N: usize> {
+    // The first `len` slots are initialized.
+    slots: [MaybeUninit<Slot<K, V>>; N],
+    len: usize,
+    head: usize,
+    tail: usize,
+}
+
+impl<K, V, const N: usize> ArrayCache<K, V, N>
+where
+    K: std::cmp::Eq,
+{
+    pub fn new() -> Self {
+        Self {
+            slots: [const { MaybeUninit::uninit() }; N],
+            len: 0,
+            head: NIL,
+            tail: NIL,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        N
+    }
+
+    fn slot(&self, index: usize) -> &Slot<K, V> {
+        debug_assert!(index < self.len);
+        unsafe { self.slots[index].assume_init_ref() }
+    }
+
+    fn slot_mut(&mut self, index: usize) -> &mut Slot<K, V> {
+        debug_assert!(index < self.len);
+        unsafe { self.slots[index].assume_init_mut() }
+    }
+
+    fn find<Q>(&self, key: &Q) -> Option<usize>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq,
+    {
+        (0..self.len).find(|&index| self.slot(index).key.borrow() == key)
+    }
+
+    fn unlink(&mut self, index: usize) {
+        let Slot { prev, next, .. } = *self.slot(index);
+
+        match prev {
+            NIL => self.head = next,
+            prev => self.slot_mut(prev).next = next,
+        }
+
+        match next {
+            NIL => self.tail = prev,
+            next => self.slot_mut(next).prev = prev,
+        }
+    }
+
+    fn link_front(&mut self, index: usize) {
+        let head = self.head;
+        let slot = self.slot_mut(index);
+        slot.prev = NIL;
+        slot.next = head;
+
+        match head {
+            NIL => self.tail = index,
+            head => self.slot_mut(head).prev = index,
+        }
+
+        self.head = index;
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq,
+    {
+        self.find(key).is_some()
+    }
+
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq,
+    {
+        let index = self.find(key)?;
+        self.unlink(index);
+        self.link_front(index);
+        Some(&self.slot(index).value)
+    }
+
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq,
+    {
+        self.find(key).map(|index| &self.slot(index).value)
+    }
+
+    /// Inserts a key-value pair, making it the most recently used entry. See
+    /// [`Cache::insert`](crate::Cache::insert) for what is returned.
+    pub fn insert(&mut self, key: K, value: V) -> Option<(K, V)> {
+        if let Some(index) = self.find(&key) {
+            let old = std::mem::replace(&mut self.slot_mut(index).value, value);
+            self.unlink(index);
+            self.link_front(index);
+            return Some((key, old));
+        }
+
+        if N == 0 {
+            return Some((key, value));
+        }
+
+        let slot = Slot {
+            key,
+            value,
+            prev: NIL,
+            next: NIL,
+        };
+
+        if self.len == N {
+            let tail = self.tail;
+            self.unlink(tail);
+            let old = std::mem::replace(self.slot_mut(tail), slot);
+            self.link_front(tail);
+            return Some((old.key, old.value));
+        }
+
+        self.slots[self.len].write(slot);
+        self.len += 1;
+        self.link_front(self.len - 1);
+        None
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq,
+    {
+        let index = self.find(key)?;
+        self.unlink(index);
+        let last = self.len - 1;
+
+        // Move the last slot into the hole to keep the initialized slots
+        // contiguous.
+        if index != last {
+            self.slots.swap(index, last);
+            let Slot { prev, next, .. } = *self.slot(index);
+
+            match prev {
+                NIL => self.head = index,
+                prev => self.slot_mut(prev).next = index,
+            }
+
+            match next {
+                NIL => self.tail = index,
+                next => self.slot_mut(next).prev = index,
+            }
+        }
+
+        self.len = last;
+        let slot = unsafe { self.slots[last].assume_init_read() };
+        Some(slot.value)
+    }
+
+    pub fn clear(&mut self) {
+        let len = std::mem::replace(&mut self.len, 0);
+        self.head = NIL;
+        self.tail = NIL;
+
+        for slot in &mut self.slots[..len] {
+            unsafe { slot.assume_init_drop() };
+        }
+    }
+
+    /// Returns an iterator over the entries, from the most recently used to
+    /// the least recently used.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        let mut cursor = self.head;
+
+        std::iter::from_fn(move || {
+            if cursor == NIL {
+                return None;
+            }
+
+            let slot = self.slot(cursor);
+            cursor = slot.next;
+            Some((&slot.key, &slot.value))
+        })
+    }
+}
+
+impl<K, V, const N: usize> Default for ArrayCache<K, V, N>
+where
+    K: std::cmp::Eq,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V, const N: usize> Drop for ArrayCache<K, V, N> {
+    fn drop(&mut self) {
+        for slot in &mut self.slots[..self.len] {
+            unsafe { slot.assume_init_drop() };
+        }
+    }
+}
+
+impl<K, V, const N: usize> fmt::Debug for ArrayCache<K, V, N>
+where
+    K: std::cmp::Eq + fmt::Debug,
+    V: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ArrayCache")
+            .field("capacity", &N)
+            .field("len", &self.len)
+            .field("entries", &Entries(self))
+            .finish()
+    }
+}
+
+struct Entries<'a, K, V, const N: usize>(&'a ArrayCache<K, V, N>);
+
+impl<K, V, const N: usize> fmt::Debug for Entries<'_, K, V, N>
+where
+    K: std::cmp::Eq + fmt::Debug,
+    V: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.0.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    struct DropCounter(Rc<Cell<usize>>);
+
+    impl Drop for DropCounter {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[test]
+    fn test_array_cache() {
+        let mut cache = ArrayCache::<_, _, 3>::new();
+
+        assert_eq!(cache.insert(1, "aws"), None);
+        assert_eq!(cache.insert(2, "gcp"), None);
+        assert_eq!(cache.insert(3, "azure"), None);
+        assert_eq!(cache.get(&1), Some(&"aws"));
+        assert_eq!(cache.insert(4, "vmware"), Some((2, "gcp")));
+        assert_eq!(cache.insert(1, "amazon"), Some((1, "aws")));
+        assert_eq!(cache.peek(&3), Some(&"azure"));
+
+        let keys: Vec<_> = cache.iter().map(|(key, _)| *key).collect();
+        assert_eq!(keys, [1, 4, 3]);
+    }
+
+    #[test]
+    fn test_array_cache_remove() {
+        let mut cache = ArrayCache::<_, _, 4>::new();
+
+        for key in 0..4 {
+            cache.insert(key, key * 10);
+        }
+
+        assert_eq!(cache.remove(&1), Some(10));
+        assert_eq!(cache.remove(&1), None);
+        cache.get(&0);
+
+        let keys: Vec<_> = cache.iter().map(|(key, _)| *key).collect();
+        assert_eq!(keys, [0, 3, 2]);
+
+        assert_eq!(cache.remove(&0), Some(0));
+        assert_eq!(cache.remove(&2), Some(20));
+        assert_eq!(cache.insert(5, 50), None);
+        assert_eq!(cache.iter().count(), 2);
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn test_array_cache_drop() {
+        let drops = Rc::new(Cell::new(0));
+        let mut cache = ArrayCache::<_, _, 2>::new();
+
+        cache.insert(1, DropCounter(drops.clone()));
+        cache.insert(2, DropCounter(drops.clone()));
+        cache.insert(3, DropCounter(drops.clone()));
+        assert_eq!(drops.get(), 1);
+
+        drop(cache);
+        assert_eq!(drops.get(), 3);
+    }
+
+    // Compares with `Cache` at N = 8 on a mix of hits, misses and evictions.
+    // Debug builds check the invariants of `Cache` after every operation, so
+    // run it with `cargo test --release -- --ignored --nocapture bench`.
+    #[test]
+    #[ignore]
+    fn bench_array_cache_against_cache() {
+        use std::hint::black_box;
+        use std::time::Instant;
+
+        const OPS: u32 = 1_000_000;
+
+        fn keys() -> impl Iterator<Item = u32> {
+            let mut state = 0x9e37_79b9_u32;
+
+            (0..OPS).map(move |_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state % 12
+            })
+        }
+
+        let mut array = ArrayCache::<u32, u32, 8>::new();
+        let start = Instant::now();
+
+        for key in keys() {
+            if array.get(&key).is_none() {
+                black_box(array.insert(key, key));
+            }
+        }
+
+        let array_time = start.elapsed();
+
+        let mut cache = crate::Cache::new(8);
+        let start = Instant::now();
+
+        for key in keys() {
+            if cache.get(&key).is_none() {
+                black_box(cache.insert(key, key));
+            }
+        }
+
+        let cache_time = start.elapsed();
+
+        println!(
+            "ArrayCache: {:.1} ns/op, Cache: {:.1} ns/op",
+            array_time.as_nanos() as f64 / f64::from(OPS),
+            cache_time.as_nanos() as f64 / f64::from(OPS),
+        );
+    }
+
+    #[test]
+    fn test_array_cache_zero_capacity() {
+        let mut cache = ArrayCache::<_, _, 0>::new();
+        assert_eq!(cache.insert(1, "aws"), Some((1, "aws")));
+        assert!(cache.is_empty());
+    }
+}
@@ -0,0 +1,104 @@
+use std::collections::hash_map::RandomState;
+use std::fmt;
+
+use crate::{Cache, DEFAULT_CAPACITY};
+
+/// Configures and creates a [`Cache`].
+///
+/// The capacity defaults to [`DEFAULT_CAPACITY`] and the hasher to
+/// [`RandomState`].
+pub struct CacheBuilder<K, V, S = RandomState> {
+    capacity: usize,
+    hasher: S,
+    on_evict: Option<Box<dyn FnMut(K, V) + Send>>,
+}
+
+impl<K, V> CacheBuilder<K, V> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<K, V, S> CacheBuilder<K, V, S> {
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    pub fn hasher<T>(self, hasher: T) -> CacheBuilder<K, V, T> {
+        CacheBuilder {
+            capacity: self.capacity,
+            hasher,
+            on_evict: self.on_evict,
+        }
+    }
+
+    /// Sets the callback passed to [`Cache::on_evict`].
+    pub fn on_evict<F>(mut self, f: F) -> Self
+    where
+        F: FnMut(K, V) + Send + 'static,
+    {
+        self.on_evict = Some(Box::new(f));
+        self
+    }
+
+    pub fn build(self) -> Cache<K, V, S>
+    where
+        K: std::cmp::Eq + std::hash::Hash + Clone,
+        S: std::hash::BuildHasher,
+    {
+        let mut cache = Cache::with_hasher(self.capacity, self.hasher);
+        cache.on_evict = self.on_evict;
+        cache
+    }
+}
+
+impl<K, V, S: Default> Default for CacheBuilder<K, V, S> {
+    fn default() -> Self {
+        Self {
+            capacity: DEFAULT_CAPACITY,
+            hasher: S::default(),
+            on_evict: None,
+        }
+    }
+}
+
+impl<K, V, S> fmt::Debug for CacheBuilder<K, V, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CacheBuilder")
+            .field("capacity", &self.capacity)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::BuildHasherDefault;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_builder() {
+        let evicted = Arc::new(Mutex::new(Vec::new()));
+        let sink = evicted.clone();
+
+        let mut cache = CacheBuilder::new()
+            .capacity(2)
+            .hasher(BuildHasherDefault::<DefaultHasher>::default())
+            .on_evict(move |key, value| sink.lock().unwrap().push((key, value)))
+            .build();
+
+        cache.insert(1, "aws");
+        cache.insert(2, "gcp");
+        assert_eq!(cache.insert(3, "azure"), None);
+        assert_eq!(*evicted.lock().unwrap(), [(1, "aws")]);
+        assert_eq!(cache.capacity(), 2);
+    }
+
+    #[test]
+    fn test_builder_defaults() {
+        let cache: Cache<i32, i32> = CacheBuilder::new().build();
+        assert_eq!(cache.capacity(), DEFAULT_CAPACITY);
+    }
+}
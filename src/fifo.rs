@@ -0,0 +1,169 @@
+use std::collections::hash_map::RandomState;
+use std::fmt;
+
+use crate::Cache;
+
+/// A cache that evicts entries in the order they were inserted.
+///
+/// Unlike [`Cache`], reads never change the eviction order: [`FifoCache::get`]
+/// is a plain lookup. Inserting an existing key replaces its value and counts
+/// as a new insertion, moving the entry to the back of the queue.
+pub struct FifoCache<K, V, S = RandomState> {
+    cache: Cache<K, V, S>,
+}
+
+impl<K, V> FifoCache<K, V>
+where
+    K: std::cmp::Eq + std::hash::Hash + Clone,
+{
+    pub fn new(capacity: usize) -> Self {
+        Self::with_hasher(capacity, RandomState::new())
+    }
+}
+
+impl<K, V, S> FifoCache<K, V, S>
+where
+    K: std::cmp::Eq + std::hash::Hash + Clone,
+    S: std::hash::BuildHasher,
+{
+    pub fn with_hasher(capacity: usize, hasher: S) -> Self {
+        Self {
+            cache: Cache::with_hasher(capacity, hasher),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cache.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.cache.capacity()
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        self.cache.contains_key(key)
+    }
+
+    /// Returns the value for the key without changing the eviction order.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        self.cache.peek(key)
+    }
+
+    /// Inserts a key-value pair at the back of the queue. See
+    /// [`Cache::insert`] for what is returned.
+    pub fn insert(&mut self, key: K, value: V) -> Option<(K, V)> {
+        self.cache.insert(key, value)
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        self.cache.remove(key)
+    }
+
+    /// Removes and returns the oldest entry.
+    pub fn pop_front(&mut self) -> Option<(K, V)> {
+        self.cache.pop_lru()
+    }
+
+    pub fn clear(&mut self) {
+        self.cache.clear();
+    }
+
+    /// Returns an iterator over the entries, from the newest to the oldest.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.cache.iter()
+    }
+}
+
+/// Takes over the entries as they are, so that the least recently used entry
+/// becomes the oldest. The capacity, stats and eviction callbacks carry over.
+impl<K, V, S> From<Cache<K, V, S>> for FifoCache<K, V, S> {
+    fn from(cache: Cache<K, V, S>) -> Self {
+        Self { cache }
+    }
+}
+
+/// Takes over the entries as they are, so that the oldest entry becomes the
+/// least recently used.
+impl<K, V, S> From<FifoCache<K, V, S>> for Cache<K, V, S> {
+    fn from(cache: FifoCache<K, V, S>) -> Self {
+        cache.cache
+    }
+}
+
+impl<K, V, S> fmt::Debug for FifoCache<K, V, S>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FifoCache")
+            .field("cache", &self.cache)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fifo_cache() {
+        let mut fifo = FifoCache::new(2);
+        let mut lru = Cache::new(2);
+
+        fifo.insert(1, "aws");
+        fifo.insert(2, "gcp");
+        lru.insert(1, "aws");
+        lru.insert(2, "gcp");
+
+        assert_eq!(fifo.get(&1), Some(&"aws"));
+        assert_eq!(lru.get(&1), Some(&"aws"));
+
+        assert_eq!(fifo.insert(3, "azure"), Some((1, "aws")));
+        assert_eq!(lru.insert(3, "azure"), Some((2, "gcp")));
+    }
+
+    #[test]
+    fn test_fifo_reinsert() {
+        let mut cache = FifoCache::new(2);
+
+        cache.insert(1, "aws");
+        cache.insert(2, "gcp");
+        assert_eq!(cache.insert(1, "amazon"), Some((1, "aws")));
+
+        assert_eq!(cache.insert(3, "azure"), Some((2, "gcp")));
+        assert_eq!(cache.pop_front(), Some((1, "amazon")));
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_from_cache() {
+        let mut lru = Cache::new(2);
+
+        lru.insert(1, "aws");
+        lru.insert(2, "gcp");
+        lru.get(&1);
+
+        let mut fifo = FifoCache::from(lru);
+        assert_eq!(fifo.insert(3, "azure"), Some((2, "gcp")));
+
+        let lru = Cache::from(fifo);
+        assert_eq!(lru.keys().collect::<Vec<_>>(), [&3, &1]);
+    }
+}
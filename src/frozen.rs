@@ -0,0 +1,131 @@
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// A read-only snapshot of a [`Cache`](crate::Cache), created by
+/// [`Cache::freeze`](crate::Cache::freeze), for loading a cache once and then
+/// reading it from many threads.
+///
+/// Lookups take `&self` and never change anything, so the snapshot is
+/// [`Send`] and [`Sync`] whenever its keys, values and hasher are, and can be
+/// read concurrently without a lock. Cloning it only bumps a reference count.
+/// The entries keep the recency order the cache had when it was frozen, but
+/// that order no longer changes.
+pub struct FrozenCache<K, V, S = RandomState> {
+    inner: Arc<Inner<K, V, S>>,
+}
+
+struct Inner<K, V, S> {
+    // From the most recently used to the least recently used.
+    entries: Vec<(K, V)>,
+    index: HashMap<K, usize, S>,
+}
+
+impl<K, V, S> FrozenCache<K, V, S>
+where
+    K: std::cmp::Eq + std::hash::Hash + Clone,
+    S: std::hash::BuildHasher,
+{
+    pub(crate) fn new(entries: Vec<(K, V)>, hasher: S) -> Self {
+        let mut index = HashMap::with_capacity_and_hasher(entries.len(), hasher);
+
+        for (i, (key, _)) in entries.iter().enumerate() {
+            index.insert(key.clone(), i);
+        }
+
+        Self {
+            inner: Arc::new(Inner { entries, index }),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.entries.is_empty()
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        self.inner.index.contains_key(key)
+    }
+
+    /// Returns the value for the key. This is the same as
+    /// [`FrozenCache::peek`], since a frozen cache has no recency order to
+    /// update.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        self.peek(key)
+    }
+
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        let &i = self.inner.index.get(key)?;
+        Some(&self.inner.entries[i].1)
+    }
+
+    /// Returns an iterator over the entries, from the most recently used to
+    /// the least recently used at the time the cache was frozen.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (&K, &V)> + ExactSizeIterator {
+        self.inner.entries.iter().map(|(key, value)| (key, value))
+    }
+}
+
+impl<K, V, S> Clone for FrozenCache<K, V, S> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<K, V, S> fmt::Debug for FrozenCache<K, V, S>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(self.inner.entries.iter().map(|(key, value)| (key, value)))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Cache;
+
+    #[test]
+    fn test_frozen_cache() {
+        let mut cache = Cache::new(3);
+
+        cache.insert(1, "aws");
+        cache.insert(2, "gcp");
+        cache.insert(3, "azure");
+        cache.get(&1);
+
+        let frozen = cache.freeze();
+        assert_eq!(frozen.len(), 3);
+        assert_eq!(frozen.get(&2), Some(&"gcp"));
+        assert_eq!(frozen.peek(&4), None);
+        assert_eq!(
+            frozen.iter().map(|(&key, _)| key).collect::<Vec<_>>(),
+            [1, 3, 2]
+        );
+
+        let shared = frozen.clone();
+        let handle = std::thread::spawn(move || shared.get(&3).copied());
+        assert_eq!(handle.join().unwrap(), Some("azure"));
+    }
+}
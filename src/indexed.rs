@@ -0,0 +1,218 @@
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::Cache;
+
+/// An LRU cache that can also look keys up by their value.
+///
+/// Alongside the cache, a second map from each value to its key is kept in
+/// sync across inserts, removals and evictions, so every entry stores a clone
+/// of its value and of its key on top of what a [`Cache`] stores. The values
+/// must therefore be hashable, and they are kept unique: inserting a value
+/// that another key already holds removes that other entry.
+pub struct ValueIndexedCache<K, V, S = RandomState> {
+    cache: Cache<K, V, S>,
+    keys: HashMap<V, K, S>,
+}
+
+impl<K, V> ValueIndexedCache<K, V>
+where
+    K: std::cmp::Eq + std::hash::Hash + Clone,
+    V: std::cmp::Eq + std::hash::Hash + Clone,
+{
+    pub fn new(capacity: usize) -> Self {
+        Self::with_hasher(capacity, RandomState::new())
+    }
+}
+
+impl<K, V, S> ValueIndexedCache<K, V, S>
+where
+    K: std::cmp::Eq + std::hash::Hash + Clone,
+    V: std::cmp::Eq + std::hash::Hash + Clone,
+    S: std::hash::BuildHasher,
+{
+    pub fn with_hasher(capacity: usize, hasher: S) -> Self
+    where
+        S: Clone,
+    {
+        Self {
+            cache: Cache::with_hasher(capacity, hasher.clone()),
+            keys: HashMap::with_hasher(hasher),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cache.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.cache.capacity()
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        self.cache.contains_key(key)
+    }
+
+    /// Returns the key holding the value, without changing the recency order.
+    pub fn key_for_value<Q>(&self, value: &Q) -> Option<&K>
+    where
+        V: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        self.keys.get(value)
+    }
+
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        self.cache.get(key)
+    }
+
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        self.cache.peek(key)
+    }
+
+    /// Inserts a key-value pair, making it the most recently used entry.
+    ///
+    /// Returns every pair displaced by the insertion: the entry of another key
+    /// that held the same value, if any, followed by the old value of the key
+    /// or the evicted entry. The passed pair is handed straight back if the
+    /// cache has zero capacity.
+    pub fn insert(&mut self, key: K, value: V) -> Vec<(K, V)> {
+        if self.cache.capacity() == 0 {
+            return vec![(key, value)];
+        }
+
+        let mut displaced = Vec::new();
+
+        if let Some(other) = self.keys.get(&value).filter(|other| **other != key) {
+            let other = other.clone();
+            self.keys.remove(&value);
+            displaced.extend(self.cache.remove(&other).map(|value| (other, value)));
+        }
+
+        let indexed = (value.clone(), key.clone());
+
+        if let Some((key, old)) = self.cache.insert(key, value) {
+            if self.keys.get(&old) == Some(&key) {
+                self.keys.remove(&old);
+            }
+
+            displaced.push((key, old));
+        }
+
+        self.keys.insert(indexed.0, indexed.1);
+        displaced
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        let value = self.cache.remove(key)?;
+        self.keys.remove(&value);
+        Some(value)
+    }
+
+    /// Removes the entry holding the value and returns its key.
+    pub fn remove_value<Q>(&mut self, value: &Q) -> Option<K>
+    where
+        V: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        let key = self.keys.remove(value)?;
+        self.cache.remove(&key);
+        Some(key)
+    }
+
+    pub fn pop_lru(&mut self) -> Option<(K, V)> {
+        let (key, value) = self.cache.pop_lru()?;
+        self.keys.remove(&value);
+        Some((key, value))
+    }
+
+    pub fn clear(&mut self) {
+        self.cache.clear();
+        self.keys.clear();
+    }
+
+    /// Returns an iterator over the entries, from the most recently used to
+    /// the least recently used.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.cache.iter()
+    }
+}
+
+impl<K, V, S> fmt::Debug for ValueIndexedCache<K, V, S>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ValueIndexedCache")
+            .field("cache", &self.cache)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_value_indexed_cache() {
+        let mut cache = ValueIndexedCache::new(2);
+
+        cache.insert(1, "aws");
+        cache.insert(2, "gcp");
+        assert_eq!(cache.key_for_value(&"aws"), Some(&1));
+
+        assert_eq!(cache.insert(3, "azure"), [(1, "aws")]);
+        assert_eq!(cache.key_for_value(&"aws"), None);
+        assert_eq!(cache.key_for_value(&"azure"), Some(&3));
+
+        assert_eq!(cache.insert(2, "google"), [(2, "gcp")]);
+        assert_eq!(cache.key_for_value(&"gcp"), None);
+        assert_eq!(cache.key_for_value(&"google"), Some(&2));
+
+        assert_eq!(cache.remove(&3), Some("azure"));
+        assert_eq!(cache.key_for_value(&"azure"), None);
+        assert_eq!(cache.remove_value(&"google"), Some(2));
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_values_stay_unique() {
+        let mut cache = ValueIndexedCache::new(3);
+
+        cache.insert(1, "aws");
+        cache.insert(2, "gcp");
+        assert_eq!(cache.insert(3, "aws"), [(1, "aws")]);
+        assert_eq!(cache.key_for_value(&"aws"), Some(&3));
+        assert_eq!(cache.len(), 2);
+
+        assert_eq!(cache.insert(3, "aws"), [(3, "aws")]);
+        assert_eq!(cache.key_for_value(&"aws"), Some(&3));
+
+        assert_eq!(cache.insert(2, "aws"), [(3, "aws"), (2, "gcp")]);
+        assert_eq!(cache.key_for_value(&"aws"), Some(&2));
+        assert_eq!(cache.key_for_value(&"gcp"), None);
+        assert_eq!(cache.len(), 1);
+    }
+}
@@ -0,0 +1,242 @@
+use std::collections::hash_map::RandomState;
+use std::fmt;
+
+use crate::Cache;
+
+/// A cache that evicts the least frequently used entry.
+///
+/// Every entry counts how many times it was accessed: inserting a new key
+/// sets its frequency to 1, and every `get` or overwriting `insert` adds one,
+/// saturating at `u64::MAX`. When the cache is full, the entry with the lowest
+/// frequency is evicted, and ties are broken by recency, so that the least
+/// recently used of those entries goes first.
+///
+/// Choosing the victim walks the entries, which makes evicting inserts
+/// `O(n)`.
+pub struct LfuCache<K, V, S = RandomState> {
+    cache: Cache<K, (V, u64), S>,
+    capacity: usize,
+}
+
+impl<K, V> LfuCache<K, V>
+where
+    K: std::cmp::Eq + std::hash::Hash + Clone,
+{
+    pub fn new(capacity: usize) -> Self {
+        Self::with_hasher(capacity, RandomState::new())
+    }
+}
+
+impl<K, V, S> LfuCache<K, V, S>
+where
+    K: std::cmp::Eq + std::hash::Hash + Clone,
+    S: std::hash::BuildHasher,
+{
+    pub fn with_hasher(capacity: usize, hasher: S) -> Self {
+        Self {
+            cache: Cache::with_hasher(usize::MAX, hasher),
+            capacity,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cache.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        self.cache.contains_key(key)
+    }
+
+    /// Returns the number of times the key was accessed.
+    pub fn frequency<Q>(&self, key: &Q) -> Option<u64>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        self.cache.peek(key).map(|(_, frequency)| *frequency)
+    }
+
+    /// Returns the value for the key, counting the access.
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        let (value, frequency) = self.cache.get_mut(key)?;
+        *frequency = frequency.saturating_add(1);
+        Some(value)
+    }
+
+    /// Returns the value for the key without counting the access.
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        self.cache.peek(key).map(|(value, _)| value)
+    }
+
+    /// Inserts a key-value pair, with the same return value as
+    /// [`Cache::insert`]: the passed key and the old value if the key was
+    /// present, the evicted entry if the cache was full, or the passed pair if
+    /// the cache has zero capacity.
+    pub fn insert(&mut self, key: K, value: V) -> Option<(K, V)> {
+        if let Some((old, frequency)) = self.cache.get_mut(&key) {
+            *frequency = frequency.saturating_add(1);
+            return Some((key, std::mem::replace(old, value)));
+        }
+
+        if self.capacity == 0 {
+            return Some((key, value));
+        }
+
+        let mut evicted = None;
+
+        if self.cache.len() >= self.capacity {
+            evicted = self.pop_lfu();
+        }
+
+        self.cache.insert(key, (value, 1));
+        evicted
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        self.cache.remove(key).map(|(value, _)| value)
+    }
+
+    /// Removes and returns the entry that would be evicted next.
+    pub fn pop_lfu(&mut self) -> Option<(K, V)> {
+        let key = self
+            .cache
+            .iter()
+            .rev()
+            .min_by_key(|(_, (_, frequency))| *frequency)
+            .map(|(key, _)| key.clone())?;
+
+        let (value, _) = self.cache.remove(&key)?;
+        Some((key, value))
+    }
+
+    pub fn clear(&mut self) {
+        self.cache.clear();
+    }
+}
+
+/// Moves the entries over in recency order, each with a frequency of 1, so
+/// that ties are broken as the cache would have evicted them. The values are
+/// moved rather than cloned, but the nodes are reallocated, and the stats and
+/// eviction callbacks are not carried over.
+impl<K, V, S> From<Cache<K, V, S>> for LfuCache<K, V, S>
+where
+    K: std::cmp::Eq + std::hash::Hash + Clone,
+    S: std::hash::BuildHasher + Clone,
+{
+    fn from(cache: Cache<K, V, S>) -> Self {
+        let mut lfu = Self::with_hasher(cache.capacity(), cache.map.hasher().clone());
+        let entries = cache.into_iter().rev();
+        lfu.cache
+            .extend(entries.map(|(key, value)| (key, (value, 1))));
+        lfu
+    }
+}
+
+/// Moves the entries over in recency order, dropping their frequencies. As
+/// with the conversion the other way, the nodes are reallocated.
+impl<K, V, S> From<LfuCache<K, V, S>> for Cache<K, V, S>
+where
+    K: std::cmp::Eq + std::hash::Hash + Clone,
+    S: std::hash::BuildHasher + Clone,
+{
+    fn from(lfu: LfuCache<K, V, S>) -> Self {
+        let mut cache = Self::with_hasher(lfu.capacity, lfu.cache.map.hasher().clone());
+        let entries = lfu.cache.into_iter().rev();
+        cache.extend(entries.map(|(key, (value, _))| (key, value)));
+        cache
+    }
+}
+
+impl<K, V, S> fmt::Debug for LfuCache<K, V, S>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LfuCache")
+            .field("capacity", &self.capacity)
+            .field("cache", &self.cache)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lfu_cache() {
+        let mut cache = LfuCache::new(3);
+
+        cache.insert(1, "aws");
+        cache.insert(2, "gcp");
+        cache.insert(3, "azure");
+        cache.get(&1);
+        cache.get(&1);
+        cache.get(&3);
+        assert_eq!(cache.frequency(&1), Some(3));
+
+        assert_eq!(cache.insert(4, "vmware"), Some((2, "gcp")));
+        assert_eq!(cache.insert(5, "val"), Some((4, "vmware")));
+
+        cache.get(&5);
+        assert_eq!(cache.insert(6, "other"), Some((3, "azure")));
+        assert_eq!(cache.peek(&1), Some(&"aws"));
+    }
+
+    #[test]
+    fn test_lfu_ties_break_by_recency() {
+        let mut cache = LfuCache::new(2);
+
+        cache.insert(1, "aws");
+        cache.insert(2, "gcp");
+        assert_eq!(cache.insert(3, "azure"), Some((1, "aws")));
+
+        assert_eq!(cache.insert(2, "google"), Some((2, "gcp")));
+        assert_eq!(cache.frequency(&2), Some(2));
+        assert_eq!(cache.remove(&3), Some("azure"));
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_from_cache() {
+        let mut lru = Cache::new(2);
+
+        lru.insert(1, "aws");
+        lru.insert(2, "gcp");
+        lru.get(&1);
+
+        let mut lfu = LfuCache::from(lru);
+        assert_eq!(lfu.frequency(&1), Some(1));
+        lfu.get(&2);
+        assert_eq!(lfu.insert(3, "azure"), Some((1, "aws")));
+
+        let lru = Cache::from(lfu);
+        assert_eq!(lru.capacity(), 2);
+        assert_eq!(lru.keys().collect::<Vec<_>>(), [&3, &2]);
+    }
+}
@@ -1,12 +1,79 @@
-use std::collections::hash_map::Entry;
+use std::collections::hash_map::RandomState;
 use std::collections::HashMap;
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::ControlFlow;
 use std::ptr::NonNull;
+use std::time::Duration;
+
+use admission::FrequencySketch;
+
+mod admission;
+mod array;
+mod builder;
+mod fifo;
+mod frozen;
+mod indexed;
+mod lfu;
+mod memory;
+mod pending;
+mod refcell;
+mod sharded;
+mod ttl;
+mod two_queue;
+mod typed;
+mod weak;
+mod weighted;
+
+pub use admission::AdmissionPolicy;
+pub use array::ArrayCache;
+pub use builder::CacheBuilder;
+pub use fifo::FifoCache;
+pub use frozen::FrozenCache;
+pub use indexed::ValueIndexedCache;
+pub use lfu::LfuCache;
+pub use memory::MemoryBoundedCache;
+pub use pending::{PendingCache, PendingLookup, Placeholder};
+pub use refcell::RefCellCache;
+pub use sharded::ShardedCache;
+pub use ttl::{Clock, SystemClock, TtlCache};
+pub use two_queue::TwoQueueCache;
+pub use weak::WeakValueCache;
+pub use weighted::{OverweightPolicy, WeighError, WeightedCache};
+
+/// The capacity of a cache created through [`Default`].
+pub const DEFAULT_CAPACITY: usize = 128;
+
+/// A hasher with a fixed seed, so that the order of a
+/// [`Cache::with_deterministic_hasher`] map is reproducible from one run to
+/// the next. Requires the `test-util` feature outside of the crate's own
+/// tests.
+#[cfg(any(test, feature = "test-util"))]
+pub type DeterministicState =
+    std::hash::BuildHasherDefault<std::collections::hash_map::DefaultHasher>;
+
+/// An operation for [`Cache::apply_op`], so that fuzzers and property tests
+/// can generate sequences of them. Requires the `test-util` feature outside
+/// of the crate's own tests.
+#[cfg(any(test, feature = "test-util"))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Op<K, V> {
+    Insert(K, V),
+    Get(K),
+    Remove(K),
+    Clear,
+    Resize(usize),
+}
+
+/// The largest capacity [`Cache::try_new`] accepts.
+pub const MAX_CAPACITY: usize = u32::MAX as usize;
 
 struct Node<K, V> {
     key: K,
     value: V,
     prev: Option<NonNull<Node<K, V>>>,
     next: Option<NonNull<Node<K, V>>>,
+    pinned: bool,
 }
 
 impl<K, V> Node<K, V> {
@@ -16,15 +83,98 @@ impl<K, V> Node<K, V> {
             value,
             prev: None,
             next: None,
+            pinned: false,
         }
     }
 }
 
-pub struct Cache<K, V> {
-    map: HashMap<K, NonNull<Node<K, V>>>,
+pub struct Cache<K, V, S = RandomState> {
+    map: HashMap<K, NonNull<Node<K, V>>, S>,
     head: Option<NonNull<Node<K, V>>>,
     tail: Option<NonNull<Node<K, V>>>,
     capacity: usize,
+    stats: CacheStats,
+    on_evict: Option<Box<dyn FnMut(K, V) + Send>>,
+    on_resize: Option<Box<dyn FnMut(usize, usize) + Send>>,
+    latency: Option<LatencyRecorder>,
+    victim_selector: Option<VictimSelector<K, V>>,
+    listeners: Vec<(ListenerId, Listener<K, V>)>,
+    next_listener_id: u64,
+    occupancy: Option<Box<[u64]>>,
+    admission: Option<FrequencySketch>,
+    generation: u64,
+    last_insert_evicted: bool,
+    adaptive: Option<AdaptiveCapacity>,
+}
+
+type VictimSelector<K, V> = Box<dyn Fn(&[(&K, &V)]) -> usize + Send + Sync>;
+
+/// How many of the coldest entries a [`Cache::with_victim_selector`] selector
+/// picks from.
+const VICTIM_WINDOW: usize = 8;
+
+/// The callback of [`Cache::with_latency_recorder_and_clock`].
+struct LatencyRecorder {
+    clock: Box<dyn Clock + Send>,
+    record: Box<dyn FnMut(Duration) + Send>,
+}
+
+/// The bounds and progress of [`Cache::with_adaptive_capacity`].
+#[derive(Debug, Clone, Copy)]
+struct AdaptiveCapacity {
+    min: usize,
+    max: usize,
+    target_hit_rate: f64,
+    // The stats when the current window started.
+    hits: u64,
+    misses: u64,
+}
+
+/// How many lookups an adaptive cache waits between adjustments.
+const ADAPTIVE_WINDOW: u64 = 1024;
+
+type Listener<K, V> = Box<dyn FnMut(&K, &V, EvictionCause) + Send>;
+
+/// Why an entry left the cache, as passed to the listeners registered with
+/// [`Cache::register_eviction_listener`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EvictionCause {
+    /// The entry was evicted to make room for a new one, because
+    /// [`Cache::resize`] shrank the cache, or by [`Cache::evict_to`].
+    Capacity,
+    /// The entry was removed by [`Cache::remove`], [`Cache::take`],
+    /// [`Cache::pop_lru`], [`Cache::retain`] or [`Cache::clear`].
+    Explicit,
+    /// The entry expired and was removed by a [`TtlCache`].
+    Expired,
+    /// The value was overwritten by inserting its key again. The listener
+    /// receives the old value.
+    Replaced,
+}
+
+/// Identifies a listener registered with
+/// [`Cache::register_eviction_listener`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ListenerId(u64);
+
+/// Counters describing how a cache has been used, as returned by
+/// [`Cache::stats`].
+///
+/// The methods that look a key up to read or fill its value count as a hit
+/// or a miss: the `get` family, `access`, `entry` and the `get_or_insert`
+/// family. Other methods that look keys up, such as `peek`, `contains_key`,
+/// `update`, `remove` or `promote`, are not counted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Lookups that found the key.
+    pub hits: u64,
+    /// Lookups that did not find the key.
+    pub misses: u64,
+    /// Entries evicted by `insert` and the other insertions to make room for
+    /// a new one. [`Cache::resize`] and [`Cache::evict_to`] don't count.
+    pub evictions: u64,
+    /// Entries added for keys that were not already present.
+    pub insertions: u64,
 }
 
 impl<K, V> Cache<K, V>
@@ -32,119 +182,5038 @@ where
     K: std::cmp::Eq + std::hash::Hash + Clone,
 {
     pub fn new(capacity: usize) -> Self {
+        Self::with_hasher(capacity, RandomState::new())
+    }
+
+    /// Like [`Cache::new`], but rejects a capacity above [`MAX_CAPACITY`].
+    pub fn try_new(capacity: usize) -> Result<Self, CapacityError> {
+        Self::try_new_with_limit(capacity, MAX_CAPACITY)
+    }
+
+    /// Like [`Cache::new`], but rejects a capacity above `limit`, or one whose
+    /// nodes could not all be allocated, as their total size would overflow
+    /// `isize::MAX` bytes.
+    pub fn try_new_with_limit(capacity: usize, limit: usize) -> Result<Self, CapacityError> {
+        let max = limit.min(isize::MAX as usize / std::mem::size_of::<Node<K, V>>());
+
+        if capacity > max {
+            return Err(CapacityError { capacity, max });
+        }
+
+        Ok(Self::new(capacity))
+    }
+
+    /// Returns a [`CacheBuilder`] for configuring a new cache.
+    pub fn builder() -> CacheBuilder<K, V> {
+        CacheBuilder::new()
+    }
+
+    /// Like [`Cache::new`], but allocates room for `capacity` entries up
+    /// front.
+    pub fn with_capacity(capacity: usize) -> Self {
+        let mut cache = Self::new(capacity);
+        cache.reserve(capacity);
+        cache
+    }
+
+    /// Creates a cache with the given capacity and inserts clones of the
+    /// items in order, so that the last one is the most recently used. If
+    /// there are more items than the capacity, the earlier ones are evicted
+    /// and only the last `capacity` distinct keys remain.
+    pub fn from_slice(capacity: usize, items: &[(K, V)]) -> Self
+    where
+        V: Clone,
+    {
+        let mut cache = Self::new(capacity);
+        cache.extend(items.iter().cloned());
+        cache
+    }
+
+    /// Creates a cache that adjusts its capacity, starting from `min`, to keep
+    /// its hit rate around `target_hit_rate`.
+    ///
+    /// Every 1024 lookups, as counted by the [`CacheStats`], the next
+    /// [`Cache::insert`] looks at the hit rate over those lookups. If it fell
+    /// short of the target, the capacity grows by a quarter, and by at least
+    /// one. If it met the target while less than half of the capacity is in
+    /// use, the capacity shrinks by a quarter, which evicts nothing. Either
+    /// way, the capacity stays within `[min, max]`, and an explicit
+    /// [`Cache::resize`] is free to leave that range.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `min` exceeds `max`, or if `target_hit_rate` is not between
+    /// 0 and 1.
+    pub fn with_adaptive_capacity(min: usize, max: usize, target_hit_rate: f64) -> Self {
+        assert!(min <= max, "the minimum capacity exceeds the maximum");
+        assert!(
+            (0.0..=1.0).contains(&target_hit_rate),
+            "the target hit rate must be between 0 and 1"
+        );
+
+        let mut cache = Self::new(min);
+        cache.adaptive = Some(AdaptiveCapacity {
+            min,
+            max,
+            target_hit_rate,
+            hits: 0,
+            misses: 0,
+        });
+        cache
+    }
+
+    /// Like [`Cache::new`], but lets new keys into the full cache according
+    /// to `policy`.
+    pub fn with_admission_policy(capacity: usize, policy: AdmissionPolicy) -> Self {
+        let mut cache = Self::new(capacity);
+        cache.set_admission_policy(policy);
+        cache
+    }
+}
+
+#[cfg(any(test, feature = "test-util"))]
+impl<K, V> Cache<K, V, DeterministicState>
+where
+    K: std::cmp::Eq + std::hash::Hash + Clone,
+{
+    /// Like [`Cache::new`], but hashes with a [`DeterministicState`] for
+    /// reproducible tests. It offers no protection against collision attacks.
+    pub fn with_deterministic_hasher(capacity: usize) -> Self {
+        Self::with_hasher(capacity, DeterministicState::default())
+    }
+}
+
+#[cfg(any(test, feature = "test-util"))]
+impl<K, V, S> Cache<K, V, S>
+where
+    K: std::cmp::Eq + std::hash::Hash + Clone,
+    S: std::hash::BuildHasher,
+{
+    /// Applies the operation through the method of the same name, dropping
+    /// whatever it returns. Pair it with [`Cache::verify`] to check the cache
+    /// after every step.
+    pub fn apply_op(&mut self, op: Op<K, V>) {
+        match op {
+            Op::Insert(key, value) => drop(self.insert(key, value)),
+            Op::Get(key) => drop(self.get(&key)),
+            Op::Remove(key) => drop(self.remove(&key)),
+            Op::Clear => self.clear(),
+            Op::Resize(capacity) => self.resize(capacity),
+        }
+    }
+}
+
+impl<K, V, S> Cache<K, V, S>
+where
+    K: std::cmp::Eq + std::hash::Hash + Clone,
+    S: std::hash::BuildHasher,
+{
+    pub fn with_hasher(capacity: usize, hasher: S) -> Self {
         Self {
-            map: HashMap::new(),
+            map: HashMap::with_hasher(hasher),
             head: None,
             tail: None,
             capacity,
+            stats: CacheStats::default(),
+            on_evict: None,
+            on_resize: None,
+            latency: None,
+            victim_selector: None,
+            listeners: Vec::new(),
+            next_listener_id: 0,
+            occupancy: None,
+            admission: None,
+            generation: 0,
+            last_insert_evicted: false,
+            adaptive: None,
         }
     }
 
-    fn remove_node(&mut self, node: &mut Node<K, V>) {
-        let prev = node.prev.take();
-        let next = node.next.take();
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
 
-        match (prev, next) {
-            (None, None) => {
-                self.head = None;
-                self.tail = None;
-            }
-            (None, Some(next)) => {
-                self.head = Some(next);
-                unsafe { (*next.as_ptr()).prev = None };
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns how full the cache is, as the ratio of its length to its
+    /// capacity. A cache with zero capacity is always empty, so its load
+    /// factor is 0.0 rather than NaN. Pinned entries can push it above 1.0.
+    pub fn load_factor(&self) -> f64 {
+        if self.capacity == 0 {
+            0.0
+        } else {
+            self.map.len() as f64 / self.capacity as f64
+        }
+    }
+
+    /// Returns how many more entries fit before an insertion evicts one. This
+    /// is 0 while pinned entries hold the cache over its capacity.
+    pub fn remaining_capacity(&self) -> usize {
+        self.capacity.saturating_sub(self.map.len())
+    }
+
+    /// Reserves room for at least `additional` more entries in the underlying
+    /// map. This does not change the capacity of the cache.
+    pub fn reserve(&mut self, additional: usize) {
+        self.map.reserve(additional);
+    }
+
+    /// Shrinks the underlying map to fit the entries it currently holds.
+    pub fn shrink_to_fit(&mut self) {
+        self.map.shrink_to_fit();
+    }
+
+    /// Returns an estimate of the bytes the cache has allocated: one node per
+    /// entry, plus a key, a pointer and a control byte for every slot the map
+    /// has room for. Allocator overhead and memory owned by the keys and
+    /// values are not included; see [`Cache::memory_footprint_with`] for the
+    /// latter.
+    pub fn memory_footprint(&self) -> usize {
+        let slot = std::mem::size_of::<(K, NonNull<Node<K, V>>)>() + 1;
+        self.map.len() * std::mem::size_of::<Node<K, V>>() + self.map.capacity() * slot
+    }
+
+    /// Like [`Cache::memory_footprint`], but adds what `heap_size` reports for
+    /// the memory owned by every entry.
+    pub fn memory_footprint_with<F>(&self, mut heap_size: F) -> usize
+    where
+        F: FnMut(&K, &V) -> usize,
+    {
+        let mut footprint = self.memory_footprint();
+
+        for (key, node) in &self.map {
+            let node = unsafe { node.as_ref() };
+            footprint += heap_size(key, &node.value);
+        }
+
+        footprint
+    }
+
+    /// Sums what `sizer` reports for every value, in no particular order.
+    pub fn total_value_bytes<F>(&self, sizer: F) -> usize
+    where
+        F: Fn(&V) -> usize,
+    {
+        self.map
+            .values()
+            .map(|node| sizer(unsafe { &node.as_ref().value }))
+            .sum()
+    }
+
+    /// Rebuilds the underlying map into a new table, with the same hasher,
+    /// sized for the entries the cache currently holds. This is worth doing
+    /// after removing many entries, such as with a large [`Cache::retain`].
+    ///
+    /// Unlike [`Cache::shrink_to_fit`], this walks the whole list, from the
+    /// most recently used entry to the least recently used, and moves each
+    /// key into the new map, so every node is checked to be reachable from
+    /// both. The recency order and the entries themselves are unchanged.
+    pub fn compact(&mut self)
+    where
+        S: Clone,
+    {
+        let mut map = HashMap::with_capacity_and_hasher(self.map.len(), self.map.hasher().clone());
+        let mut cursor = self.head;
+
+        while let Some(node) = cursor {
+            let (key, node) = self
+                .map
+                .remove_entry(unsafe { &node.as_ref().key })
+                .expect("a node in the list is missing from the map");
+            map.insert(key, node);
+            cursor = unsafe { node.as_ref().next };
+        }
+
+        assert!(
+            self.map.is_empty(),
+            "an entry in the map is missing from the list"
+        );
+        self.map = map;
+
+        #[cfg(debug_assertions)]
+        self.check_invariants();
+    }
+
+    /// Changes the capacity, evicting the least recently used entries if the
+    /// cache holds more than the new capacity. Pinned entries are never
+    /// evicted, so the cache stays over capacity if there are more of them
+    /// than the new capacity.
+    ///
+    /// If the capacity changes, the [`Cache::on_resize`] callback is called
+    /// after the evictions.
+    pub fn resize(&mut self, capacity: usize) {
+        self.keep_hottest(capacity);
+    }
+
+    /// Like [`Cache::resize`] with a capacity of `n`, keeping the `n` most
+    /// recently used entries, but returns the evicted entries, from the least
+    /// recently used.
+    pub fn keep_hottest(&mut self, n: usize) -> Vec<(K, V)> {
+        let old = std::mem::replace(&mut self.capacity, n);
+        let evicted = self.evict_to(n);
+
+        if let Some(on_resize) = self.on_resize.as_mut().filter(|_| old != n) {
+            on_resize(old, n);
+        }
+
+        evicted
+    }
+
+    /// Removes every entry, as [`Cache::clear`] does, and only then changes
+    /// the capacity, as [`Cache::resize`] does, so that nothing is evicted.
+    /// The callbacks, listeners and stats are kept.
+    pub fn reinit(&mut self, capacity: usize) {
+        self.clear();
+        self.resize(capacity);
+    }
+
+    /// Evicts the least recently used entries until at most `target_len` are
+    /// left, and returns them in eviction order. Unlike [`Cache::resize`],
+    /// this leaves the capacity unchanged. Pinned entries are skipped, so more
+    /// than `target_len` entries remain if that many are pinned.
+    pub fn evict_to(&mut self, target_len: usize) -> Vec<(K, V)> {
+        let mut evicted = Vec::new();
+
+        while self.map.len() > target_len {
+            let Some(victim) = self.victim() else {
+                break;
+            };
+
+            self.remove_node(victim);
+            self.map.remove(unsafe { &(*victim.as_ptr()).key });
+            self.generation += 1;
+            let victim = unsafe { Box::from_raw(victim.as_ptr()) };
+            self.notify(&victim.key, &victim.value, EvictionCause::Capacity);
+            evicted.push((victim.key, victim.value));
+        }
+
+        evicted
+    }
+
+    fn assert_insertable(&self) {
+        assert!(
+            self.capacity > 0,
+            "cannot insert into a zero-capacity cache"
+        );
+        assert!(
+            self.map.len() < self.capacity || self.victim().is_some(),
+            "cannot insert into a cache full of pinned entries"
+        );
+    }
+
+    /// Returns the least recently used entry that isn't pinned.
+    fn victim(&self) -> Option<NonNull<Node<K, V>>> {
+        let mut cursor = self.tail;
+
+        while let Some(node) = cursor {
+            let node = unsafe { node.as_ref() };
+
+            if !node.pinned {
+                break;
             }
-            (Some(prev), None) => {
-                self.tail = Some(prev);
-                unsafe { (*prev.as_ptr()).next = None };
+
+            cursor = node.prev;
+        }
+
+        cursor
+    }
+
+    /// Returns the entry an insertion into the full cache evicts, as picked by
+    /// the victim selector if there is one.
+    fn insertion_victim(&self) -> Option<NonNull<Node<K, V>>> {
+        let Some(selector) = &self.victim_selector else {
+            return self.victim();
+        };
+
+        let mut candidates = Vec::with_capacity(VICTIM_WINDOW);
+        let mut cursor = self.tail;
+
+        while let Some(node) = cursor.filter(|_| candidates.len() < VICTIM_WINDOW) {
+            let node = unsafe { node.as_ref() };
+
+            if !node.pinned {
+                candidates.push(node);
             }
-            (Some(prev), Some(next)) => unsafe {
-                (*prev.as_ptr()).next = Some(next);
-                (*next.as_ptr()).prev = Some(prev);
-            },
+
+            cursor = node.prev;
+        }
+
+        if candidates.is_empty() {
+            return None;
         }
+
+        let entries: Vec<_> = candidates
+            .iter()
+            .map(|node| (&node.key, &node.value))
+            .collect();
+        let node = candidates
+            .get(selector(&entries))
+            .expect("the victim selector returned an index outside of the candidates");
+
+        Some(NonNull::from(*node))
+    }
+
+    /// Protects the key from eviction by `insert` and `resize`, returning
+    /// whether it was present. A pinned entry can still be removed explicitly,
+    /// by `remove`, `pop_lru` or `retain`.
+    ///
+    /// Once every entry of a full cache is pinned, `insert` rejects new keys
+    /// and hands them straight back, as a cache with zero capacity does, and
+    /// the methods that insert and return a reference to the new value, such
+    /// as [`Cache::get_or_insert_with`], panic.
+    pub fn pin<Q>(&mut self, key: &Q) -> bool
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        self.set_pinned(key, true)
     }
 
-    fn add_node(&mut self, node: &mut Node<K, V>) {
-        node.prev = None;
-        node.next = self.head;
+    /// Makes the key evictable again, returning whether it was present.
+    pub fn unpin<Q>(&mut self, key: &Q) -> bool
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        self.set_pinned(key, false)
+    }
 
-        if let Some(head) = self.head {
-            unsafe { (*head.as_ptr()).prev = Some(node.into()) };
+    pub fn is_pinned<Q>(&self, key: &Q) -> bool
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        self.map
+            .get(key)
+            .is_some_and(|node| unsafe { node.as_ref().pinned })
+    }
+
+    fn set_pinned<Q>(&mut self, key: &Q, pinned: bool) -> bool
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        let Some(&node) = self.map.get(key) else {
+            return false;
+        };
+
+        unsafe { (*node.as_ptr()).pinned = pinned };
+        true
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        self.map.contains_key(key)
+    }
+
+    /// Checks that the list and the map agree, returning the first
+    /// inconsistency found. The walks are bounded by the length of the map, so
+    /// this finishes in linear time even if the list has a cycle.
+    pub fn verify(&self) -> Result<(), CacheError> {
+        let mut len = 0;
+        let mut prev = None;
+        let mut cursor = self.head;
+
+        while let Some(node) = cursor {
+            if len == self.map.len() {
+                return Err(CacheError::LengthMismatch {
+                    map: self.map.len(),
+                    list: len + 1,
+                });
+            }
+
+            let node_ref = unsafe { node.as_ref() };
+
+            if node_ref.prev != prev {
+                return Err(CacheError::BrokenBackwardLink);
+            }
+
+            if self.map.get(&node_ref.key) != Some(&node) {
+                return Err(CacheError::KeyMismatch);
+            }
+
+            len += 1;
+            prev = cursor;
+            cursor = node_ref.next;
         }
 
-        self.head = Some(node.into());
+        if len != self.map.len() {
+            return Err(CacheError::LengthMismatch {
+                map: self.map.len(),
+                list: len,
+            });
+        }
 
-        if self.tail.is_none() {
-            self.tail = Some(node.into());
+        if prev != self.tail {
+            return Err(CacheError::BrokenForwardLink);
+        }
+
+        let mut next = None;
+        let mut cursor = self.tail;
+
+        // The forward walk already bounded the list, and every node on it has
+        // a matching `prev` link, so this walk retraces it.
+        while let Some(node) = cursor {
+            let node_ref = unsafe { node.as_ref() };
+
+            if node_ref.next != next {
+                return Err(CacheError::BrokenForwardLink);
+            }
+
+            next = cursor;
+            cursor = node_ref.prev;
+        }
+
+        if next != self.head {
+            return Err(CacheError::BrokenBackwardLink);
+        }
+
+        for (key, node) in &self.map {
+            if unsafe { &node.as_ref().key } != key {
+                return Err(CacheError::KeyMismatch);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Panics if [`Cache::verify`] fails. Debug builds run this after every
+    /// `insert`, `get` and `remove`, which makes them linear in the length of
+    /// the cache.
+    #[cfg(debug_assertions)]
+    fn check_invariants(&self) {
+        if let Err(error) = self.verify() {
+            panic!("{error}");
         }
     }
 
-    pub fn get(&mut self, key: &K) -> Option<&V> {
-        if let Some(node) = self.map.get(key) {
-            let node = unsafe { node.as_ptr().as_mut().unwrap() };
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        self.get_mut(key).map(|value| &*value)
+    }
+
+    /// Like [`Cache::get`], but takes the key by value, for callers that
+    /// would otherwise have to keep it alive just to borrow it. The key is
+    /// dropped before returning.
+    pub fn get_owned(&mut self, key: K) -> Option<&V> {
+        self.get(&key)
+    }
+
+    /// Like [`Cache::get`], but also returns where the entry stood in the
+    /// recency order before it was promoted: 0 for the most recently used
+    /// entry, up to `len() - 1` for the least recently used. Finding the rank
+    /// walks the list from the most recently used entry.
+    pub fn get_with_rank<Q>(&mut self, key: &Q) -> Option<(&V, usize)>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        let rank = self.map.get(key).map(|&node| self.rank_of(node));
+        Some((self.get(key)?, rank?))
+    }
+
+    /// Like [`Cache::get`], but reports a missing key as a [`CacheMiss`].
+    pub fn try_get<Q>(&mut self, key: &Q) -> Result<&V, CacheMiss>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        self.get(key).ok_or(CacheMiss)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        self.record_access(key);
+        let node = self.map.get(key).copied();
+
+        if let Some(node) = node {
             self.remove_node(node);
             self.add_node(node);
-            Some(&node.value)
+            self.stats.hits += 1;
         } else {
-            None
+            self.stats.misses += 1;
         }
+
+        #[cfg(debug_assertions)]
+        self.check_invariants();
+
+        node.map(|node| unsafe { &mut (*node.as_ptr()).value })
     }
 
-    pub fn insert(&mut self, key: K, value: V) {
-        match self.map.entry(key.clone()) {
-            Entry::Occupied(entry) => {
-                let node = unsafe { entry.get().as_ptr().as_mut().unwrap() };
-                node.value = value;
-                self.remove_node(node);
-                self.add_node(node);
-            }
-            Entry::Vacant(_entry) => {
-                if self.map.len() == self.capacity {
-                    let tail = self.tail.take().unwrap();
-                    let node = unsafe { tail.as_ptr().as_mut().unwrap() };
+    /// Like [`Cache::get`], but leaves the recency order untouched. Unlike
+    /// [`Cache::peek`], the lookup is still counted in the [`CacheStats`].
+    pub fn get_without_promote<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        self.record_access(key);
+
+        let Some(node) = self.map.get(key) else {
+            self.stats.misses += 1;
+            return None;
+        };
+
+        self.stats.hits += 1;
+        Some(unsafe { &node.as_ref().value })
+    }
+
+    /// Like [`Cache::get`], but makes the entry the least recently used
+    /// instead, so that it is the first to be evicted, as with
+    /// [`Cache::demote`].
+    pub fn get_and_demote<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        self.record_access(key);
+
+        let Some(&node) = self.map.get(key) else {
+            self.stats.misses += 1;
+            return None;
+        };
+
+        self.remove_node(node);
+        self.add_node_back(node);
+        self.stats.hits += 1;
+
+        #[cfg(debug_assertions)]
+        self.check_invariants();
+
+        Some(unsafe { &node.as_ref().value })
+    }
+
+    /// Looks up the key and returns a guard to its value. The entry is only
+    /// promoted to the most recently used when the guard is dropped, so a
+    /// guard that is leaked, for example through [`std::mem::forget`], leaves
+    /// the recency order untouched. The lookup is counted in the
+    /// [`CacheStats`] either way.
+    pub fn access<Q>(&mut self, key: &Q) -> Option<AccessGuard<'_, K, V, S>>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        self.record_access(key);
+
+        let Some(&node) = self.map.get(key) else {
+            self.stats.misses += 1;
+            return None;
+        };
+
+        self.stats.hits += 1;
+        Some(AccessGuard { cache: self, node })
+    }
+
+    /// Looks up several keys at once, returning their values in the order of
+    /// `keys`. Every key found is promoted in turn, so that the last one found
+    /// ends up the most recently used entry, and each lookup counts towards
+    /// the [`CacheStats`].
+    pub fn get_many<'a, Q, I>(&mut self, keys: I) -> Vec<Option<&V>>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash + 'a,
+        I: IntoIterator<Item = &'a Q>,
+    {
+        let nodes: Vec<_> = keys
+            .into_iter()
+            .map(|key| {
+                self.record_access(key);
+                let node = self.map.get(key).copied();
+
+                if let Some(node) = node {
                     self.remove_node(node);
-                    self.map.remove(&node.key);
+                    self.add_node(node);
+                    self.stats.hits += 1;
+                } else {
+                    self.stats.misses += 1;
                 }
 
-                let node = Box::new(Node::new(key.clone(), value));
+                node
+            })
+            .collect();
 
-                let node = unsafe {
-                    let ptr = Box::into_raw(node);
-                    &mut *ptr
-                };
+        nodes
+            .into_iter()
+            .map(|node| node.map(|node| unsafe { &node.as_ref().value }))
+            .collect()
+    }
+
+    /// Returns mutable references to the values of several keys at once.
+    ///
+    /// Returns `None` if any key is missing or appears more than once, in
+    /// which case the order of the entries is left untouched. Otherwise every
+    /// key is promoted in turn, so that the last one ends up the most recently
+    /// used entry, and each counts as a hit.
+    pub fn get_many_mut<Q, const N: usize>(&mut self, keys: [&Q; N]) -> Option<[&mut V; N]>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        let mut nodes = [NonNull::dangling(); N];
 
-                let node = NonNull::new(node).unwrap();
-                self.map.insert(key, node);
-                self.add_node(unsafe { node.as_ptr().as_mut().unwrap() });
+        for (i, key) in keys.into_iter().enumerate() {
+            self.record_access(key);
+
+            let Some(&node) = self.map.get(key) else {
+                self.stats.misses += 1;
+                return None;
+            };
+
+            if nodes[..i].contains(&node) {
+                return None;
             }
+
+            nodes[i] = node;
+        }
+
+        for node in nodes {
+            self.remove_node(node);
+            self.add_node(node);
         }
+
+        self.stats.hits += N as u64;
+        Some(nodes.map(|node| unsafe { &mut (*node.as_ptr()).value }))
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Returns the keys of the entries on either side of the key in the
+    /// recency order: the more recently used one, then the less recently used
+    /// one. Returns `None` if the key is missing.
+    pub fn neighbors<Q>(&self, key: &Q) -> Option<(Option<&K>, Option<&K>)>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        let node = self.map.get(key)?.as_ptr();
 
-    #[test]
-    fn test_cache() {
-        let mut cache = Cache::new(3);
+        unsafe {
+            let prev = (*node).prev.map(|prev| &(*prev.as_ptr()).key);
+            let next = (*node).next.map(|next| &(*next.as_ptr()).key);
+            Some((prev, next))
+        }
+    }
 
-        cache.insert(1, "aws".to_owned());
-        assert_eq!(cache.get(&1), Some(&"aws".to_owned()));
+    /// Makes the key the most recently used entry, returning whether it was
+    /// present.
+    #[doc(alias = "touch")]
+    pub fn promote<Q>(&mut self, key: &Q) -> bool
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        if let Some(&node) = self.map.get(key) {
+            self.remove_node(node);
+            self.add_node(node);
+            true
+        } else {
+            false
+        }
+    }
 
-        cache.insert(2, "gcp".to_owned());
-        cache.insert(3, "azure".to_owned());
-        assert_eq!(cache.get(&3), Some(&"azure".to_owned()));
+    /// Refreshes the recency of the key without borrowing its value, as a
+    /// heartbeat would, returning whether it was present. This is
+    /// [`Cache::promote`] under another name: the order changes exactly as
+    /// with [`Cache::get`], but the [`CacheStats`] are not updated.
+    pub fn touch<Q>(&mut self, key: &Q) -> bool
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        self.promote(key)
+    }
 
-        cache.insert(4, "vmware".to_owned());
-        assert_eq!(cache.get(&2), Some(&"gcp".to_owned()));
-        assert_eq!(cache.get(&1), None);
+    /// Makes the key the least recently used entry, returning whether it was
+    /// present.
+    pub fn demote<Q>(&mut self, key: &Q) -> bool
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        if let Some(&node) = self.map.get(key) {
+            self.remove_node(node);
+            self.add_node_back(node);
+            true
+        } else {
+            false
+        }
+    }
 
-        cache.insert(5, "val".to_owned());
-        assert_eq!(cache.get(&5), Some(&"val".to_owned()));
-        assert_eq!(cache.get(&4), Some(&"vmware".to_owned()));
-        assert_eq!(cache.get(&3), None);
-        assert_eq!(cache.get(&2), Some(&"gcp".to_owned()));
-        assert_eq!(cache.get(&1), None);
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        let node = self.map.get(key)?;
+        Some(unsafe { &node.as_ref().value })
+    }
+
+    /// Like [`Cache::peek`], but returns a mutable reference. Neither the
+    /// recency order nor the [`CacheStats`] are affected.
+    pub fn peek_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        let &node = self.map.get(key)?;
+        Some(unsafe { &mut (*node.as_ptr()).value })
+    }
+
+    /// Inserts a key-value pair, making it the most recently used entry.
+    ///
+    /// If the key was already present, its value is replaced and the passed
+    /// key is returned together with the old value. Otherwise, if the cache
+    /// was full, the least recently used entry is evicted and returned, unless
+    /// a callback registered with [`Cache::on_evict`] takes it instead. A
+    /// cache with zero capacity stores nothing and hands the pair straight
+    /// back, as does a full cache whose [`AdmissionPolicy`] turns the key
+    /// away.
+    pub fn insert(&mut self, key: K, value: V) -> Option<(K, V)> {
+        self.insert_checked(key, value, true, false)
+    }
+
+    // The shared path of `insert`, `push` and `insert_lru`.
+    fn insert_checked(&mut self, key: K, value: V, notify: bool, at_lru: bool) -> Option<(K, V)> {
+        self.adapt_capacity();
+
+        if !self.admits(&key) {
+            self.last_insert_evicted = false;
+            return Some((key, value));
+        }
+
+        self.insert_admitted(key, value, notify, at_lru)
+    }
+
+    fn adapt_capacity(&mut self) {
+        let Some(adaptive) = &mut self.adaptive else {
+            return;
+        };
+
+        let hits = self.stats.hits - adaptive.hits;
+        let lookups = hits + self.stats.misses - adaptive.misses;
+
+        if lookups < ADAPTIVE_WINDOW {
+            return;
+        }
+
+        adaptive.hits = self.stats.hits;
+        adaptive.misses = self.stats.misses;
+
+        let hit_rate = hits as f64 / lookups as f64;
+        let step = (self.capacity / 4).max(1);
+
+        let capacity = if hit_rate < adaptive.target_hit_rate {
+            self.capacity.saturating_add(step).min(adaptive.max)
+        } else if self.map.len() < self.capacity / 2 {
+            (self.capacity - step).max(adaptive.min)
+        } else {
+            return;
+        };
+
+        self.resize(capacity);
+    }
+
+    /// Inserts a key-value pair only if the key is missing, evicting the least
+    /// recently used entry as [`Cache::insert`] would. If the key is present,
+    /// or the cache cannot take it, the pair is handed back in `Err`, and the
+    /// existing entry is neither promoted nor overwritten.
+    ///
+    /// Since the key was missing, no old value is ever replaced, so the
+    /// `Option` in `Ok` only carries an eviction: it holds the entry evicted
+    /// to make room if the cache was full, and is `None` if there was room, or
+    /// if a callback registered with [`Cache::on_evict`] took the evicted
+    /// entry. The eviction listeners see the evicted entry in every case.
+    pub fn insert_unique(&mut self, key: K, value: V) -> Result<Option<(K, V)>, (K, V)> {
+        if self.map.contains_key(&key) || !self.admits(&key) {
+            return Err((key, value));
+        }
+
+        let full = self.map.len() >= self.capacity;
+
+        if self.capacity == 0 || (full && self.victim().is_none()) {
+            return Err((key, value));
+        }
+
+        Ok(self.insert_admitted(key, value, true, false))
+    }
+
+    // Inserts without consulting the admission policy, for the callers that
+    // hand out a reference to the new value.
+    fn insert_admitted(&mut self, key: K, value: V, notify: bool, at_lru: bool) -> Option<(K, V)> {
+        let evictions = self.stats.evictions;
+        let displaced = self.insert_node(key, value, notify, at_lru);
+        self.last_insert_evicted = self.stats.evictions > evictions;
+        self.record_occupancy();
+
+        #[cfg(debug_assertions)]
+        self.check_invariants();
+
+        displaced
+    }
+
+    /// Like [`Cache::insert`], but always returns the displaced pair, even
+    /// when a callback is registered with [`Cache::on_evict`]:
+    ///
+    /// - if the key was present, the passed key and the old value;
+    /// - if the cache was full, the evicted least recently used entry;
+    /// - if the cache has zero capacity, or the [`AdmissionPolicy`] turns the
+    ///   key away, the passed pair itself;
+    /// - otherwise, `None`.
+    pub fn push(&mut self, key: K, value: V) -> Option<(K, V)> {
+        self.insert_checked(key, value, false, false)
+    }
+
+    /// Like [`Cache::insert`], but makes the entry the least recently used
+    /// one, so that it is the next to be evicted. This is meant for seeding
+    /// the cache with low-priority entries.
+    pub fn insert_lru(&mut self, key: K, value: V) -> Option<(K, V)> {
+        self.insert_checked(key, value, true, true)
+    }
+
+    /// Inserts the pair as [`Cache::insert`] does if `admit` accepts it, and
+    /// drops it otherwise, returning whether it was stored. `admit` is
+    /// consulted for keys that are already present too, so a rejected pair
+    /// leaves the old value in place, without promoting it. A pair `admit`
+    /// accepts can still be turned away by the cache itself, as by
+    /// [`Cache::insert`], in which case this returns `false` too.
+    pub fn insert_if<F>(&mut self, key: K, value: V, admit: F) -> bool
+    where
+        F: FnOnce(&K, &V) -> bool,
+    {
+        if !admit(&key, &value) {
+            return false;
+        }
+
+        self.insert_stored(key, value)
+    }
+
+    // Inserts the pair and returns whether the cache holds the key afterwards,
+    // since `insert` hands back both rejected pairs and replaced values.
+    fn insert_stored(&mut self, key: K, value: V) -> bool {
+        let inserted = key.clone();
+        self.insert(key, value);
+        self.map.contains_key(&inserted)
+    }
+
+    /// Like [`Cache::insert`], unless the key is present with a value equal to
+    /// `value`, in which case the entry is only promoted, and neither the
+    /// eviction listeners nor the stats see an update. Returns whether the
+    /// value was stored, which is not the case either when [`Cache::insert`]
+    /// turns the pair away.
+    pub fn insert_if_changed(&mut self, key: K, value: V) -> bool
+    where
+        V: PartialEq,
+    {
+        if self.peek(&key) == Some(&value) {
+            self.promote(&key);
+            return false;
+        }
+
+        self.insert_stored(key, value)
+    }
+
+    /// Inserts every pair in turn and returns the entries evicted along the
+    /// way, in eviction order. Like [`Cache::push`], the evicted entries are
+    /// returned even when a callback is registered with [`Cache::on_evict`].
+    ///
+    /// Old values replaced by a pair for the same key are dropped rather than
+    /// returned, while pairs that a zero-capacity cache cannot hold, or that
+    /// the [`AdmissionPolicy`] turns away, are returned as evicted.
+    pub fn insert_many<I>(&mut self, items: I) -> Vec<(K, V)>
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let mut evicted = Vec::new();
+
+        for (key, value) in items {
+            let replaces = self.map.contains_key(&key);
+
+            if let Some(displaced) = self.push(key, value).filter(|_| !replaces) {
+                evicted.push(displaced);
+            }
+        }
+
+        evicted
+    }
+
+    /// Inserts every pair in turn without checking for room, for bulk-loading
+    /// a cache that is known to have enough of it. Pairs for keys already
+    /// present replace their values, which are dropped, and neither the
+    /// admission policy nor the eviction listeners are consulted.
+    ///
+    /// Debug builds panic if the pairs don't fit. Release builds insert them
+    /// anyway, leaving the cache over its capacity, as pinned entries can,
+    /// until entries are removed or [`Cache::resize`] evicts them.
+    pub fn fill_unchecked(&mut self, items: impl IntoIterator<Item = (K, V)>) {
+        let items = items.into_iter();
+        self.reserve(items.size_hint().0);
+
+        for (key, value) in items {
+            self.generation += 1;
+
+            if let Some(&node) = self.map.get(&key) {
+                unsafe { (*node.as_ptr()).value = value };
+                self.remove_node(node);
+                self.add_node(node);
+                continue;
+            }
+
+            debug_assert!(
+                self.map.len() < self.capacity,
+                "`fill_unchecked` exceeded the capacity of the cache"
+            );
+
+            let node = Box::new(Node::new(key.clone(), value));
+            let node = unsafe { NonNull::new_unchecked(Box::into_raw(node)) };
+            self.map.insert(key, node);
+            self.add_node(node);
+            self.stats.insertions += 1;
+        }
+
+        #[cfg(debug_assertions)]
+        self.check_invariants();
+    }
+
+    /// Replaces the value of a key that is already present, making it the most
+    /// recently used entry, and returns the old value. Unlike
+    /// [`Cache::insert`], a missing key is left missing.
+    pub fn update<Q>(&mut self, key: &Q, value: V) -> Option<V>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        let &node = self.map.get(key)?;
+        self.remove_node(node);
+        self.add_node(node);
+        Some(std::mem::replace(
+            unsafe { &mut (*node.as_ptr()).value },
+            value,
+        ))
+    }
+
+    /// Like [`Cache::update`], but also returns the rank the entry had before
+    /// it was promoted, as with [`Cache::get_with_rank`]. A missing key is
+    /// inserted as if by [`Cache::insert`] instead, and `None` is returned.
+    pub fn replace_with_rank(&mut self, key: K, value: V) -> Option<(V, usize)> {
+        let Some(&node) = self.map.get(&key) else {
+            self.insert(key, value);
+            return None;
+        };
+
+        let rank = self.rank_of(node);
+        Some((self.update(&key, value)?, rank))
+    }
+
+    /// Like [`Cache::update`], but leaves the entry where it is in the recency
+    /// order, for refreshing a value without making it look recently used.
+    pub fn update_in_place<Q>(&mut self, key: &Q, value: V) -> Option<V>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        self.peek_mut(key).map(|old| std::mem::replace(old, value))
+    }
+
+    /// Returns the [`AdmissionPolicy`] new keys are let in by.
+    pub fn admission_policy(&self) -> AdmissionPolicy {
+        match self.admission {
+            Some(_) => AdmissionPolicy::TinyLfu,
+            None => AdmissionPolicy::Always,
+        }
+    }
+
+    /// Sets the [`AdmissionPolicy`] used by [`Cache::insert`], forgetting the
+    /// access counts gathered so far. The sketch behind
+    /// [`AdmissionPolicy::TinyLfu`] is sized for the current capacity.
+    pub fn set_admission_policy(&mut self, policy: AdmissionPolicy) {
+        self.admission = match policy {
+            AdmissionPolicy::Always => None,
+            AdmissionPolicy::TinyLfu => Some(FrequencySketch::new(self.capacity)),
+        };
+    }
+
+    fn record_access<Q>(&mut self, key: &Q)
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::hash::Hash,
+    {
+        if let Some(sketch) = &mut self.admission {
+            sketch.increment(self.map.hasher().hash_one(key));
+        }
+    }
+
+    // Counts the insertion as an access, then decides whether the key may
+    // evict the next victim, if it would have to.
+    fn admits(&mut self, key: &K) -> bool {
+        if self.admission.is_none() {
+            return true;
+        }
+
+        self.record_access(key);
+
+        if self.map.len() < self.capacity || self.map.contains_key(key) {
+            return true;
+        }
+
+        let Some(victim) = self.insertion_victim() else {
+            return true;
+        };
+
+        let hasher = self.map.hasher();
+        let victim = hasher.hash_one(unsafe { &(*victim.as_ptr()).key });
+        let candidate = hasher.hash_one(key);
+
+        let Some(sketch) = &self.admission else {
+            return true;
+        };
+
+        sketch.estimate(candidate) > sketch.estimate(victim)
+    }
+
+    fn insert_node(&mut self, key: K, value: V, notify: bool, at_lru: bool) -> Option<(K, V)> {
+        // Look the key up by reference first, so that updating an existing
+        // entry doesn't clone it.
+        if let Some(&node) = self.map.get(&key) {
+            let old = std::mem::replace(unsafe { &mut (*node.as_ptr()).value }, value);
+            self.remove_node(node);
+            self.link(node, at_lru);
+            self.generation += 1;
+            self.notify(&key, &old, EvictionCause::Replaced);
+            return Some((key, old));
+        }
+
+        if self.capacity == 0 {
+            return Some((key, value));
+        }
+
+        let mut evicted = None;
+
+        let full = self.map.len() >= self.capacity;
+
+        let node = match self.insertion_victim().filter(|_| full) {
+            // Reuse the allocation of the evicted entry for the new one.
+            Some(victim) => {
+                self.remove_node(victim);
+                self.map.remove(unsafe { &(*victim.as_ptr()).key });
+                let (old_key, old_value) = unsafe {
+                    let victim = victim.as_ptr();
+                    (
+                        std::mem::replace(&mut (*victim).key, key.clone()),
+                        std::mem::replace(&mut (*victim).value, value),
+                    )
+                };
+                evicted = Some((old_key, old_value));
+                self.stats.evictions += 1;
+                victim
+            }
+            None if full => return Some((key, value)),
+            None => {
+                let node = Box::new(Node::new(key.clone(), value));
+                unsafe { NonNull::new_unchecked(Box::into_raw(node)) }
+            }
+        };
+
+        self.map.insert(key, node);
+        self.link(node, at_lru);
+        self.generation += 1;
+        self.stats.insertions += 1;
+
+        if let Some((key, value)) = &evicted {
+            self.notify(key, value, EvictionCause::Capacity);
+        }
+
+        if let Some(on_evict) = self.on_evict.as_mut().filter(|_| notify) {
+            if let Some((key, value)) = evicted.take() {
+                on_evict(key, value);
+            }
+        }
+
+        evicted
+    }
+
+    /// Returns the value for the key, inserting `default` if it is missing.
+    /// Either way, the entry becomes the most recently used, and on a hit
+    /// `default` is dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key is missing and the cache has zero capacity.
+    pub fn get_or_insert(&mut self, key: K, default: V) -> &mut V {
+        self.entry(key).or_insert(default)
+    }
+
+    /// Adds `by` to the value for the key, starting from `V::default()` if it
+    /// is missing, and returns the sum. Either way, the entry becomes the most
+    /// recently used, and a new key may evict the least recently used entry.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key is missing and the cache has zero capacity.
+    pub fn increment(&mut self, key: K, by: V) -> &V
+    where
+        V: Default + std::ops::AddAssign + Copy,
+    {
+        let value = self.entry(key).or_insert_with(V::default);
+        *value += by;
+        value
+    }
+
+    /// Returns the value for the key, computing and inserting it with `f` if
+    /// it is missing. Either way, the entry becomes the most recently used.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key is missing and the cache has zero capacity.
+    pub fn get_or_insert_with<F>(&mut self, key: K, f: F) -> &V
+    where
+        F: FnOnce() -> V,
+    {
+        self.get_or_insert_with_mut(key, f)
+    }
+
+    /// Returns the value for the key without promoting it, or else inserts the
+    /// result of `f` as the least recently used entry, as
+    /// [`Cache::insert_lru`] does. Neither a hit nor a miss makes the entry
+    /// look recently used, which suits speculative, low-priority entries.
+    /// Since it hands out the new value, a miss bypasses the
+    /// [`AdmissionPolicy`], as the entry methods do.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key is missing and the cache has zero capacity.
+    pub fn peek_or_insert_with<F>(&mut self, key: K, f: F) -> &V
+    where
+        F: FnOnce() -> V,
+    {
+        if let Some(&node) = self.map.get(&key) {
+            return unsafe { &(*node.as_ptr()).value };
+        }
+
+        self.assert_insertable();
+        self.insert_admitted(key, f(), true, true);
+        unsafe { &(*self.tail.unwrap().as_ptr()).value }
+    }
+
+    /// Like [`Cache::get_or_insert_with`], but passes the key and `arg` to
+    /// `f`, so that `f` can work with state the caller owns without capturing
+    /// it. `f` only runs on a miss, and `arg` is dropped on a hit.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key is missing and the cache has zero capacity.
+    pub fn get_or_insert_with_arg<A, F>(&mut self, key: K, arg: A, f: F) -> &V
+    where
+        F: FnOnce(&K, A) -> V,
+    {
+        self.entry(key).or_insert_with_key(|key| f(key, arg))
+    }
+
+    /// Like [`Cache::get_or_insert_with`], but returns a mutable reference.
+    pub fn get_or_insert_with_mut<F>(&mut self, key: K, f: F) -> &mut V
+    where
+        F: FnOnce() -> V,
+    {
+        let missing = !self.map.contains_key(&key);
+
+        if let Some(latency) = self.latency.as_mut().filter(|_| missing) {
+            // Time the miss before looking the key up as an entry, which
+            // borrows the whole cache, and leave the cache untouched if `f`
+            // panics.
+            let start = latency.clock.now();
+            let value = f();
+            (latency.record)(latency.clock.now().duration_since(start));
+            return self.entry(key).or_insert(value);
+        }
+
+        self.entry(key).or_insert_with(f)
+    }
+
+    /// Like [`Cache::get_or_insert_with`], but looks the key up by reference
+    /// and only builds the owned key with `make_key` if it is missing.
+    /// Neither closure runs on a hit.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key is missing and the cache has zero capacity.
+    pub fn get_or_insert_ref<Q, KF, VF>(&mut self, key: &Q, make_key: KF, make_val: VF) -> &V
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+        KF: FnOnce() -> K,
+        VF: FnOnce() -> V,
+    {
+        self.record_access(key);
+
+        if let Some(&node) = self.map.get(key) {
+            self.remove_node(node);
+            self.add_node(node);
+            self.stats.hits += 1;
+            return unsafe { &node.as_ref().value };
+        }
+
+        self.stats.misses += 1;
+        self.assert_insertable();
+
+        let owned = make_key();
+        debug_assert!(owned.borrow() == key, "`make_key` built a different key");
+        self.insert_admitted(owned, make_val(), true, false);
+        unsafe { &self.head.unwrap().as_ref().value }
+    }
+
+    /// Like [`Cache::get_or_insert_with`], but also returns whether the key
+    /// was missing and `f` had to compute the value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key is missing and the cache has zero capacity.
+    pub fn get_or_insert_with_status<F>(&mut self, key: K, f: F) -> (&V, bool)
+    where
+        F: FnOnce() -> V,
+    {
+        let entry = self.entry(key);
+        let missed = entry.node.is_none();
+        (entry.or_insert_with(f), missed)
+    }
+
+    /// Like [`Cache::get_or_insert_with_status`], but returns a mutable
+    /// reference, and tells a hit from an insertion with an [`Occupancy`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key is missing and the cache has zero capacity.
+    pub fn get_mut_or_insert<F>(&mut self, key: K, default: F) -> (&mut V, Occupancy)
+    where
+        F: FnOnce() -> V,
+    {
+        let entry = self.entry(key);
+        let occupancy = match entry.node {
+            Some(_) => Occupancy::Hit,
+            None => Occupancy::Inserted,
+        };
+        (entry.or_insert_with(default), occupancy)
+    }
+
+    /// Like [`Cache::get_or_insert_with`], but for a computation that can
+    /// fail. If `f` returns an error, nothing is inserted and the error is
+    /// passed on.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key is missing, `f` succeeds, and the cache has zero
+    /// capacity.
+    pub fn get_or_try_insert_with<F, E>(&mut self, key: K, f: F) -> Result<&V, E>
+    where
+        F: FnOnce() -> Result<V, E>,
+    {
+        self.entry(key).or_try_insert_with(f).map(|value| &*value)
+    }
+
+    /// Gets the entry for the key for in-place manipulation. If the key is
+    /// present, it becomes the most recently used.
+    pub fn entry(&mut self, key: K) -> CacheEntry<'_, K, V, S> {
+        self.record_access(&key);
+        let node = self.map.get(&key).copied();
+
+        if let Some(node) = node {
+            self.remove_node(node);
+            self.add_node(node);
+            self.stats.hits += 1;
+        } else {
+            self.stats.misses += 1;
+        }
+
+        CacheEntry {
+            cache: self,
+            key,
+            node,
+        }
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        self.remove_with_cause(key, EvictionCause::Explicit)
+    }
+
+    pub(crate) fn remove_with_cause<Q>(&mut self, key: &Q, cause: EvictionCause) -> Option<V>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        let node = self.map.remove(key)?;
+        self.remove_node(node);
+        self.generation += 1;
+        self.record_occupancy();
+
+        #[cfg(debug_assertions)]
+        self.check_invariants();
+
+        let node = unsafe { Box::from_raw(node.as_ptr()) };
+        self.notify(&node.key, &node.value, cause);
+        Some(node.value)
+    }
+
+    /// Removes the entry for the key and returns its value. This is the same
+    /// as [`Cache::remove`], for call sites that read better when the value
+    /// is being consumed.
+    pub fn take<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        self.remove(key)
+    }
+
+    /// Swaps the values of two keys, leaving the recency order untouched.
+    /// Returns `false`, leaving the cache untouched, if either key is missing.
+    /// Swapping a key with itself does nothing.
+    pub fn swap_values<Q>(&mut self, a: &Q, b: &Q) -> bool
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        let (Some(&a), Some(&b)) = (self.map.get(a), self.map.get(b)) else {
+            return false;
+        };
+
+        if a != b {
+            unsafe { std::ptr::swap(&mut (*a.as_ptr()).value, &mut (*b.as_ptr()).value) };
+        }
+
+        true
+    }
+
+    /// Changes the key of the entry for `old` to `new`, keeping its value and
+    /// its place in the recency order. Returns `false`, leaving the cache
+    /// untouched, if `old` is missing or `new` is already present, which
+    /// includes `new` being equal to `old`.
+    pub fn rekey<Q>(&mut self, old: &Q, new: K) -> bool
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        if self.map.contains_key::<K>(&new) {
+            return false;
+        }
+
+        let Some(node) = self.map.remove(old) else {
+            return false;
+        };
+
+        self.map.insert(new.clone(), node);
+        unsafe { (*node.as_ptr()).key = new };
+        self.generation += 1;
+        true
+    }
+
+    /// Moves the entry for the key into `dest` as if by [`Cache::insert`],
+    /// and reports what became of it as a [`Transfer`]: the entry `dest`
+    /// displaced, as `insert` would return it, or the entry itself if `dest`
+    /// turned it away. Either way, the entry has left this cache.
+    ///
+    /// This returns a [`Transfer`] rather than whether the key existed, since
+    /// a `bool` would leave the caller no way to recover an entry that `dest`
+    /// evicted to make room or rejected outright, and both would be dropped.
+    /// [`Transfer::Missing`] is the `false` case.
+    pub fn transfer_to<Q, T>(&mut self, key: &Q, dest: &mut Cache<K, V, T>) -> Transfer<K, V>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+        T: std::hash::BuildHasher,
+    {
+        let Some(node) = self.map.remove(key) else {
+            return Transfer::Missing;
+        };
+
+        self.remove_node(node);
+        self.generation += 1;
+        let node = unsafe { Box::from_raw(node.as_ptr()) };
+        // A new key comes back from `insert` only if it was not stored.
+        let present = dest.contains_key(key);
+
+        match dest.insert(node.key, node.value) {
+            Some((key_back, value)) if !present && key_back.borrow() == key => {
+                Transfer::Rejected(key_back, value)
+            }
+            displaced => Transfer::Moved(displaced),
+        }
+    }
+
+    /// Moves every entry of `other` into the cache as if by [`Cache::insert`],
+    /// from its least recently used to its most recently used, so that the
+    /// entries keep their relative order. On a conflict, the value from
+    /// `other` wins.
+    pub fn merge<T>(&mut self, other: Cache<K, V, T>) {
+        self.extend(other.into_iter().rev());
+    }
+
+    /// Replaces the contents of the cache with `items`, inserted in order as
+    /// if by [`Cache::insert`]. The current entries are all dropped before the
+    /// first new one is inserted.
+    pub fn replace_all<I>(&mut self, items: I)
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        self.clear();
+        self.extend(items);
+    }
+
+    /// Removes every entry for which `f` returns `false`, keeping the recency
+    /// order of the others.
+    pub fn retain<F>(&mut self, f: F)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        self.retain_with_cause(f, EvictionCause::Explicit);
+    }
+
+    pub(crate) fn retain_with_cause<F>(&mut self, mut f: F, cause: EvictionCause)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        let mut current = self.head;
+
+        while let Some(node) = current {
+            let (key, value) = unsafe {
+                current = (*node.as_ptr()).next;
+                (&(*node.as_ptr()).key, &mut (*node.as_ptr()).value)
+            };
+
+            if !f(key, value) {
+                self.remove_node(node);
+                self.map.remove(unsafe { &(*node.as_ptr()).key });
+                self.generation += 1;
+                let node = unsafe { Box::from_raw(node.as_ptr()) };
+                self.notify(&node.key, &node.value, cause);
+            }
+        }
+    }
+
+    // Removes the entry that inserting a new key into the full cache would
+    // evict, if `f` returns `true` for it, so that it is reported with `cause`
+    // rather than as a capacity eviction.
+    pub(crate) fn remove_insertion_victim_if<F>(&mut self, f: F, cause: EvictionCause) -> bool
+    where
+        F: FnOnce(&K, &V) -> bool,
+    {
+        if self.map.len() < self.capacity {
+            return false;
+        }
+
+        let Some(node) = self.insertion_victim() else {
+            return false;
+        };
+
+        if !f(unsafe { &node.as_ref().key }, unsafe {
+            &node.as_ref().value
+        }) {
+            return false;
+        }
+
+        self.remove_node(node);
+        self.map.remove(unsafe { &(*node.as_ptr()).key });
+        self.generation += 1;
+        self.record_occupancy();
+
+        #[cfg(debug_assertions)]
+        self.check_invariants();
+
+        let node = unsafe { Box::from_raw(node.as_ptr()) };
+        self.notify(&node.key, &node.value, cause);
+        true
+    }
+
+    // Like `retain_with_cause`, but only visits up to `budget` entries, from
+    // the least recently used one.
+    pub(crate) fn retain_lru_with_cause<F>(&mut self, budget: usize, mut f: F, cause: EvictionCause)
+    where
+        F: FnMut(&K, &mut V) -> bool,
+    {
+        let mut current = self.tail;
+
+        for _ in 0..budget {
+            let Some(node) = current else {
+                break;
+            };
+
+            let (key, value) = unsafe {
+                current = (*node.as_ptr()).prev;
+                (&(*node.as_ptr()).key, &mut (*node.as_ptr()).value)
+            };
+
+            if !f(key, value) {
+                self.remove_node(node);
+                self.map.remove(unsafe { &(*node.as_ptr()).key });
+                self.generation += 1;
+                let node = unsafe { Box::from_raw(node.as_ptr()) };
+                self.notify(&node.key, &node.value, cause);
+            }
+        }
+    }
+
+    /// Removes every entry for which `f` returns `true` and returns them, from
+    /// the most recently used to the least recently used, leaving the others
+    /// in order. As with [`Cache::drain`], the eviction listeners are not
+    /// called for the entries handed back.
+    pub fn drain_filter<F>(&mut self, mut f: F) -> Vec<(K, V)>
+    where
+        F: FnMut(&K, &V) -> bool,
+    {
+        let mut drained = Vec::new();
+        let mut current = self.head;
+
+        while let Some(node) = current {
+            let (key, value) = unsafe {
+                current = (*node.as_ptr()).next;
+                (&(*node.as_ptr()).key, &(*node.as_ptr()).value)
+            };
+
+            if f(key, value) {
+                self.remove_node(node);
+                self.map.remove(key);
+                self.generation += 1;
+                let node = unsafe { Box::from_raw(node.as_ptr()) };
+                drained.push((node.key, node.value));
+            }
+        }
+
+        #[cfg(debug_assertions)]
+        self.check_invariants();
+
+        drained
+    }
+
+    /// Consumes the cache into a [`FrozenCache`] holding the same entries in
+    /// the same recency order, which can no longer be changed but can be
+    /// shared between threads and read without a lock. The callbacks and
+    /// listeners are dropped without being notified.
+    pub fn freeze(mut self) -> FrozenCache<K, V, S>
+    where
+        S: Clone,
+    {
+        let hasher = self.map.hasher().clone();
+        FrozenCache::new(self.take_nodes().collect(), hasher)
+    }
+
+    /// Moves every entry for which `f` returns `true` into a new cache with
+    /// the same capacity and hasher, keeping their relative recency order.
+    /// The nodes are moved rather than reallocated, and pinned entries stay
+    /// pinned. The new cache starts with fresh stats and without callbacks.
+    pub fn split_off<F>(&mut self, mut f: F) -> Self
+    where
+        F: FnMut(&K, &V) -> bool,
+        S: Clone,
+    {
+        let mut other = Self::with_hasher(self.capacity, self.map.hasher().clone());
+        let mut current = self.head;
+
+        while let Some(node) = current {
+            let (key, value) = unsafe {
+                current = (*node.as_ptr()).next;
+                (&(*node.as_ptr()).key, &(*node.as_ptr()).value)
+            };
+
+            if f(key, value) {
+                self.remove_node(node);
+                self.map.remove(key);
+                self.generation += 1;
+                other.generation += 1;
+                other.map.insert(key.clone(), node);
+                other.add_node_back(node);
+            }
+        }
+
+        #[cfg(debug_assertions)]
+        {
+            self.check_invariants();
+            other.check_invariants();
+        }
+
+        other
+    }
+
+    /// Registers a callback that receives the entries `insert` evicts to make
+    /// room, replacing any previous one. It is not called when the value of
+    /// an existing key is overwritten.
+    pub fn on_evict<F>(&mut self, f: F)
+    where
+        F: FnMut(K, V) + Send + 'static,
+    {
+        self.on_evict = Some(Box::new(f));
+    }
+
+    /// Registers a callback that receives the old and the new capacity
+    /// whenever [`Cache::resize`], or the adjustments of
+    /// [`Cache::with_adaptive_capacity`], change the capacity, replacing any
+    /// previous one.
+    pub fn on_resize<F>(&mut self, f: F)
+    where
+        F: FnMut(usize, usize) + Send + 'static,
+    {
+        self.on_resize = Some(Box::new(f));
+    }
+
+    /// Makes [`Cache::get_or_insert_with`] and
+    /// [`Cache::get_or_insert_with_mut`] report how long `f` took whenever
+    /// they run it on a miss, replacing any previous recorder. Hits are not
+    /// reported.
+    pub fn with_latency_recorder<F>(self, f: F) -> Self
+    where
+        F: FnMut(Duration) + Send + 'static,
+    {
+        self.with_latency_recorder_and_clock(f, SystemClock)
+    }
+
+    /// Lets `f` pick the entry that an insertion into the full cache evicts,
+    /// such as the one that is cheapest to recompute, replacing any previous
+    /// selector.
+    ///
+    /// `f` is handed up to 8 of the least recently used entries that aren't
+    /// pinned, the least recently used first, and returns the index of the
+    /// one to evict. It is only consulted when `insert` and the other
+    /// insertions make room for a new key; [`Cache::resize`],
+    /// [`Cache::evict_to`] and [`Cache::pop_lru`] still take the least
+    /// recently used entry.
+    ///
+    /// # Panics
+    ///
+    /// An insertion panics, before changing the cache, if `f` returns an
+    /// index out of bounds.
+    pub fn with_victim_selector<F>(mut self, f: F) -> Self
+    where
+        F: Fn(&[(&K, &V)]) -> usize + Send + Sync + 'static,
+    {
+        self.victim_selector = Some(Box::new(f));
+        self
+    }
+
+    /// Like [`Cache::with_latency_recorder`], but measures with the given
+    /// clock.
+    pub fn with_latency_recorder_and_clock<F, C>(mut self, f: F, clock: C) -> Self
+    where
+        F: FnMut(Duration) + Send + 'static,
+        C: Clock + Send + 'static,
+    {
+        self.latency = Some(LatencyRecorder {
+            clock: Box::new(clock),
+            record: Box::new(f),
+        });
+        self
+    }
+
+    /// Registers a listener that receives a clone of every entry that leaves
+    /// the cache, together with the [`EvictionCause`], including through
+    /// `push` and `insert_many`. Entries handed to the caller by
+    /// [`Cache::drain`] or by consuming the cache, and those freed when the
+    /// cache is dropped, are not reported. Listeners are called in
+    /// registration order, before an evicted entry reaches the
+    /// [`Cache::on_evict`] callback or the caller.
+    pub fn register_eviction_listener<F>(&mut self, mut f: F) -> ListenerId
+    where
+        F: FnMut(K, V, EvictionCause) + Send + 'static,
+        V: Clone,
+    {
+        self.add_listener(Box::new(move |key, value, cause| {
+            f(key.clone(), value.clone(), cause)
+        }))
+    }
+
+    pub(crate) fn add_listener(&mut self, listener: Listener<K, V>) -> ListenerId {
+        let id = ListenerId(self.next_listener_id);
+        self.next_listener_id += 1;
+        self.listeners.push((id, listener));
+        id
+    }
+
+    /// Removes a listener, returning whether it was registered.
+    pub fn remove_eviction_listener(&mut self, id: ListenerId) -> bool {
+        let len = self.listeners.len();
+        self.listeners.retain(|(listener, _)| *listener != id);
+        self.listeners.len() != len
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    /// Returns a counter that changes whenever an entry is inserted, removed
+    /// or evicted, or the cache is cleared, but not when entries are looked
+    /// up, promoted or mutated in place. Comparing it with an earlier snapshot
+    /// tells whether a key looked up back then might have been displaced
+    /// since, in which case it should be looked up again.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Returns whether the last insertion of a key, be it by [`Cache::insert`],
+    /// [`Cache::push`], an entry or any other method that goes through the
+    /// eviction policy, evicted an entry to make room, even if the entry went
+    /// to the [`Cache::on_evict`] callback rather than back to the caller.
+    /// [`Cache::fill_unchecked`] leaves this untouched.
+    pub fn last_insert_evicted(&self) -> bool {
+        self.last_insert_evicted
+    }
+
+    /// Returns the number of entries, for sampling how full the cache is over
+    /// time.
+    pub fn sample_occupancy(&self) -> usize {
+        self.map.len()
+    }
+
+    /// Starts recording how full the cache is after every `insert`,
+    /// `insert_lru` and `remove`, into a histogram of `buckets` equal ranges
+    /// of the capacity, discarding any previous recording. The last bucket
+    /// also counts a full cache. The histogram is allocated here, once.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `buckets` is 0.
+    pub fn enable_occupancy_histogram(&mut self, buckets: usize) {
+        assert!(
+            buckets > 0,
+            "an occupancy histogram needs at least one bucket"
+        );
+        self.occupancy = Some(vec![0; buckets].into_boxed_slice());
+    }
+
+    pub fn disable_occupancy_histogram(&mut self) {
+        self.occupancy = None;
+    }
+
+    /// Returns the number of samples recorded in each bucket, from the
+    /// emptiest to the fullest, if recording is enabled.
+    pub fn occupancy_histogram(&self) -> Option<&[u64]> {
+        self.occupancy.as_deref()
+    }
+
+    fn record_occupancy(&mut self) {
+        let Some(histogram) = &mut self.occupancy else {
+            return;
+        };
+
+        let buckets = histogram.len();
+        let bucket = match self.capacity {
+            0 => 0,
+            capacity => (self.map.len() as u128 * buckets as u128 / capacity as u128) as usize,
+        };
+
+        histogram[bucket.min(buckets - 1)] += 1;
+    }
+
+    /// Zeroes the [`CacheStats`]. An adaptive cache also starts its current
+    /// window of lookups over.
+    pub fn reset_stats(&mut self) {
+        self.stats = CacheStats::default();
+
+        if let Some(adaptive) = &mut self.adaptive {
+            adaptive.hits = 0;
+            adaptive.misses = 0;
+        }
+    }
+
+    /// Returns the least recently used entry, which is the next one to be
+    /// evicted, without removing it or changing its recency.
+    pub fn peek_lru(&self) -> Option<(&K, &V)> {
+        let node = unsafe { self.tail?.as_ref() };
+        Some((&node.key, &node.value))
+    }
+
+    /// Returns the key of the least recently used entry, which is the next one
+    /// to be evicted.
+    pub fn lru_key(&self) -> Option<&K> {
+        Some(unsafe { &self.tail?.as_ref().key })
+    }
+
+    /// Returns the key of the most recently used entry.
+    pub fn mru_key(&self) -> Option<&K> {
+        Some(unsafe { &self.head?.as_ref().key })
+    }
+
+    /// Returns the keys of the most and of the least recently used entries,
+    /// which are the same key if there is only one entry.
+    pub fn extremes(&self) -> Option<(&K, &K)> {
+        Some((self.mru_key()?, self.lru_key()?))
+    }
+
+    /// Returns the hash of each key, from the least recently used to the most
+    /// recently used, to compare the order of two caches without cloning
+    /// their keys.
+    ///
+    /// The hashes come from the hasher of the cache, so they only stay the
+    /// same across runs with a fixed hasher, such as the one of
+    /// [`Cache::with_deterministic_hasher`].
+    pub fn order_fingerprint(&self) -> Vec<u64> {
+        let hasher = self.map.hasher();
+        self.iter_lru()
+            .map(|(key, _)| hasher.hash_one(key))
+            .collect()
+    }
+
+    /// Removes and returns the least recently used entry.
+    pub fn pop_lru(&mut self) -> Option<(K, V)> {
+        let tail = self.tail?;
+        self.remove_node(tail);
+        self.map.remove(unsafe { &(*tail.as_ptr()).key });
+        self.generation += 1;
+        let node = unsafe { Box::from_raw(tail.as_ptr()) };
+        self.notify(&node.key, &node.value, EvictionCause::Explicit);
+        Some((node.key, node.value))
+    }
+}
+
+impl<K, V, S> Cache<K, V, S> {
+    /// Removes every entry, keeping the capacity, and returns them as an
+    /// iterator. Entries that are not consumed are freed when the iterator is
+    /// dropped.
+    pub fn drain(&mut self) -> Drain<'_, K, V> {
+        Drain {
+            iter: self.take_nodes(),
+            marker: PhantomData,
+        }
+    }
+
+    /// Consumes the cache into its entries, from the least recently used to
+    /// the most recently used.
+    pub fn into_lru_vec(mut self) -> Vec<(K, V)> {
+        self.take_nodes().rev().collect()
+    }
+
+    fn take_nodes(&mut self) -> IntoIter<K, V> {
+        // The iterator takes over the nodes, leaving the cache with an empty
+        // list and map.
+        let len = self.map.len();
+        self.map.clear();
+        self.generation += 1;
+
+        IntoIter {
+            head: self.head.take(),
+            tail: self.tail.take(),
+            len,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.map.clear();
+        self.generation += 1;
+
+        let mut current = self.head.take();
+        self.tail = None;
+
+        while let Some(node) = current {
+            let node = unsafe { Box::from_raw(node.as_ptr()) };
+            current = node.next;
+            self.notify(&node.key, &node.value, EvictionCause::Explicit);
+        }
+    }
+
+    fn notify(&mut self, key: &K, value: &V, cause: EvictionCause) {
+        for (_, listener) in &mut self.listeners {
+            listener(key, value, cause);
+        }
+    }
+
+    /// Returns an iterator over the entries, from the most recently used to
+    /// the least recently used. The recency order is left untouched.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            head: self.head,
+            tail: self.tail,
+            len: self.map.len(),
+            marker: PhantomData,
+        }
+    }
+
+    /// Returns an iterator over the entries with mutable references to the
+    /// values, in the same order as [`Cache::iter`]. The recency order is left
+    /// untouched.
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        IterMut {
+            head: self.head,
+            tail: self.tail,
+            len: self.map.len(),
+            marker: PhantomData,
+        }
+    }
+
+    /// Calls `f` on each entry, from the most recently used to the least
+    /// recently used, until it returns [`ControlFlow::Break`]. The recency
+    /// order is left untouched.
+    pub fn for_each<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K, &mut V) -> ControlFlow<()>,
+    {
+        let _ = self.iter_mut().try_for_each(|(key, value)| f(key, value));
+    }
+
+    /// Returns up to `n` of the most recently used entries, most recent first.
+    pub fn most_recent(&self, n: usize) -> Vec<(&K, &V)> {
+        self.iter().take(n).collect()
+    }
+
+    /// Returns up to `n` of the least recently used entries, least recent
+    /// first.
+    pub fn least_recent(&self, n: usize) -> Vec<(&K, &V)> {
+        self.iter_lru().take(n).collect()
+    }
+
+    /// Returns how many entries `f` returns `true` for, without changing the
+    /// recency order.
+    pub fn count_matching<F>(&self, f: F) -> usize
+    where
+        F: Fn(&K, &V) -> bool,
+    {
+        self.iter().filter(|(key, value)| f(key, value)).count()
+    }
+
+    /// Returns the key at the given rank in the recency order, as reported by
+    /// [`Cache::get_with_rank`], without changing the order. This walks the
+    /// list from the most recently used entry, so it takes O(`rank`) time.
+    pub fn key_at_rank(&self, rank: usize) -> Option<&K> {
+        self.keys().nth(rank)
+    }
+
+    /// Splits the keys into `n` contiguous buckets, from the most recently
+    /// used to the least recently used. The buckets differ in size by at most
+    /// one, with the larger ones first, so that if `n` exceeds the length the
+    /// trailing buckets are empty.
+    pub fn recency_buckets(&self, n: usize) -> Vec<Vec<&K>> {
+        let len = self.map.len();
+        let mut keys = self.keys();
+
+        (0..n)
+            .map(|bucket| {
+                let size = len / n + usize::from(bucket < len % n);
+                keys.by_ref().take(size).collect()
+            })
+            .collect()
+    }
+
+    /// Returns an iterator over the entries, from the least recently used to
+    /// the most recently used, which is the order they would be evicted in.
+    pub fn iter_lru(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.iter().rev()
+    }
+
+    /// Returns an iterator over the keys, in the same order as [`Cache::iter`].
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.iter().map(|(key, _)| key)
+    }
+
+    /// Returns an iterator over the values, in the same order as [`Cache::iter`].
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.iter().map(|(_, value)| value)
+    }
+
+    /// Returns clones of the entries, in the same order as [`Cache::iter`].
+    pub fn to_vec(&self) -> Vec<(K, V)>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        self.iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect()
+    }
+
+    /// Returns clones of the keys, without touching the values, from the least
+    /// recently used to the most recently used.
+    pub fn key_set(&self) -> Vec<K>
+    where
+        K: Clone,
+    {
+        self.iter_lru().map(|(key, _)| key.clone()).collect()
+    }
+
+    // The list helpers only ever go through the raw pointers handed out by
+    // `Box::into_raw`, so that no reference to a node outlives the operation
+    // that created it and invalidates the pointers held by its neighbours.
+
+    fn rank_of(&self, node: NonNull<Node<K, V>>) -> usize {
+        let mut rank = 0;
+        let mut cursor = self.head;
+
+        while cursor != Some(node) {
+            cursor = cursor.and_then(|node| unsafe { node.as_ref().next });
+            rank += 1;
+        }
+
+        rank
+    }
+
+    fn remove_node(&mut self, node: NonNull<Node<K, V>>) {
+        let (prev, next) = unsafe {
+            let node = node.as_ptr();
+            ((*node).prev.take(), (*node).next.take())
+        };
+
+        match (prev, next) {
+            (None, None) => {
+                self.head = None;
+                self.tail = None;
+            }
+            (None, Some(next)) => {
+                self.head = Some(next);
+                unsafe { (*next.as_ptr()).prev = None };
+            }
+            (Some(prev), None) => {
+                self.tail = Some(prev);
+                unsafe { (*prev.as_ptr()).next = None };
+            }
+            (Some(prev), Some(next)) => unsafe {
+                (*prev.as_ptr()).next = Some(next);
+                (*next.as_ptr()).prev = Some(prev);
+            },
+        }
+    }
+
+    fn add_node(&mut self, node: NonNull<Node<K, V>>) {
+        unsafe {
+            (*node.as_ptr()).prev = None;
+            (*node.as_ptr()).next = self.head;
+        }
+
+        if let Some(head) = self.head {
+            unsafe { (*head.as_ptr()).prev = Some(node) };
+        }
+
+        self.head = Some(node);
+
+        if self.tail.is_none() {
+            self.tail = Some(node);
+        }
+    }
+
+    fn link(&mut self, node: NonNull<Node<K, V>>, at_lru: bool) {
+        if at_lru {
+            self.add_node_back(node);
+        } else {
+            self.add_node(node);
+        }
+    }
+
+    fn add_node_back(&mut self, node: NonNull<Node<K, V>>) {
+        unsafe {
+            (*node.as_ptr()).next = None;
+            (*node.as_ptr()).prev = self.tail;
+        }
+
+        if let Some(tail) = self.tail {
+            unsafe { (*tail.as_ptr()).next = Some(node) };
+        }
+
+        self.tail = Some(node);
+
+        if self.head.is_none() {
+            self.head = Some(node);
+        }
+    }
+}
+
+impl<K, V, S> Drop for Cache<K, V, S> {
+    fn drop(&mut self) {
+        self.listeners.clear();
+        self.clear();
+    }
+}
+
+/// A view into a single entry of a cache, returned by [`Cache::entry`].
+///
+/// The `or_insert` methods only hand back a reference to the value. If the
+/// cache is full, the entry they evict to make room is passed to the
+/// listeners and to the callback registered with [`Cache::on_evict`], and is
+/// dropped if there is none; use [`Cache::insert_unique`] to get it back.
+pub struct CacheEntry<'a, K, V, S = RandomState> {
+    cache: &'a mut Cache<K, V, S>,
+    key: K,
+    node: Option<NonNull<Node<K, V>>>,
+}
+
+impl<'a, K, V, S> CacheEntry<'a, K, V, S>
+where
+    K: std::cmp::Eq + std::hash::Hash + Clone,
+    S: std::hash::BuildHasher,
+{
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Returns the value, inserting `default` if the entry is vacant.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the entry is vacant and the cache has zero capacity.
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        self.or_insert_with(|| default)
+    }
+
+    /// Returns the value, inserting the result of `f` if the entry is vacant.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the entry is vacant and the cache has zero capacity.
+    pub fn or_insert_with<F>(self, f: F) -> &'a mut V
+    where
+        F: FnOnce() -> V,
+    {
+        match self.or_try_insert_with(|| Ok::<_, std::convert::Infallible>(f())) {
+            Ok(value) => value,
+            Err(never) => match never {},
+        }
+    }
+
+    /// Returns the value, inserting the result of `f`, which is passed the
+    /// key, if the entry is vacant.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the entry is vacant and the cache has zero capacity.
+    pub fn or_insert_with_key<F>(self, f: F) -> &'a mut V
+    where
+        F: FnOnce(&K) -> V,
+    {
+        match self.node {
+            Some(node) => unsafe { &mut (*node.as_ptr()).value },
+            None => {
+                let value = f(&self.key);
+                self.cache.assert_insertable();
+                self.cache.insert_admitted(self.key, value, true, false);
+                unsafe { &mut self.cache.head.unwrap().as_mut().value }
+            }
+        }
+    }
+
+    /// Returns the value, inserting the result of `f` if the entry is vacant
+    /// and `f` succeeds. If `f` fails, nothing is inserted.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the entry is vacant, `f` succeeds, and the cache has zero
+    /// capacity.
+    pub fn or_try_insert_with<F, E>(self, f: F) -> Result<&'a mut V, E>
+    where
+        F: FnOnce() -> Result<V, E>,
+    {
+        match self.node {
+            Some(node) => Ok(unsafe { &mut (*node.as_ptr()).value }),
+            None => {
+                let value = f()?;
+                self.cache.assert_insertable();
+                self.cache.insert_admitted(self.key, value, true, false);
+                Ok(unsafe { &mut self.cache.head.unwrap().as_mut().value })
+            }
+        }
+    }
+
+    /// Applies `f` to the value if the entry is occupied.
+    pub fn and_modify<F>(self, f: F) -> Self
+    where
+        F: FnOnce(&mut V),
+    {
+        if let Some(node) = self.node {
+            f(unsafe { &mut (*node.as_ptr()).value });
+        }
+
+        self
+    }
+}
+
+/// A guard to a cached value, returned by [`Cache::access`], that promotes
+/// the entry when dropped.
+pub struct AccessGuard<'a, K, V, S = RandomState> {
+    cache: &'a mut Cache<K, V, S>,
+    node: NonNull<Node<K, V>>,
+}
+
+impl<K, V, S> AccessGuard<'_, K, V, S> {
+    pub fn key(&self) -> &K {
+        unsafe { &(*self.node.as_ptr()).key }
+    }
+}
+
+impl<K, V, S> std::ops::Deref for AccessGuard<'_, K, V, S> {
+    type Target = V;
+
+    fn deref(&self) -> &V {
+        unsafe { &(*self.node.as_ptr()).value }
+    }
+}
+
+impl<K, V, S> Drop for AccessGuard<'_, K, V, S> {
+    fn drop(&mut self) {
+        self.cache.remove_node(self.node);
+        self.cache.add_node(self.node);
+    }
+}
+
+/// Whether [`Cache::get_mut_or_insert`] found the key or inserted it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Occupancy {
+    Hit,
+    Inserted,
+}
+
+/// What [`Cache::transfer_to`] did with the entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Transfer<K, V> {
+    /// The key was not in the source cache.
+    Missing,
+    /// The entry was moved, displacing the given entry of the destination,
+    /// which is returned as by [`Cache::insert`].
+    Moved(Option<(K, V)>),
+    /// The destination could not hold the entry, because it has zero
+    /// capacity, is full of pinned entries, or its [`AdmissionPolicy`] turned
+    /// the key away, so the entry is handed back.
+    Rejected(K, V),
+}
+
+/// The error returned by [`Cache::try_get`] when the key is not cached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheMiss;
+
+impl fmt::Display for CacheMiss {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("key not found in cache")
+    }
+}
+
+impl std::error::Error for CacheMiss {}
+
+/// An inconsistency found by [`Cache::verify`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheError {
+    /// The map and the list hold different numbers of entries.
+    LengthMismatch { map: usize, list: usize },
+    /// Following the `next` links from the head does not lead to the tail.
+    BrokenForwardLink,
+    /// Following the `prev` links from the tail does not lead to the head.
+    BrokenBackwardLink,
+    /// A node is not mapped under its own key.
+    KeyMismatch,
+}
+
+impl fmt::Display for CacheError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::LengthMismatch { map, list } => {
+                write!(f, "the map holds {map} entries but the list {list}")
+            }
+            Self::BrokenForwardLink => f.write_str("broken forward link in the list"),
+            Self::BrokenBackwardLink => f.write_str("broken backward link in the list"),
+            Self::KeyMismatch => f.write_str("node is not mapped under its key"),
+        }
+    }
+}
+
+impl std::error::Error for CacheError {}
+
+/// The error returned by [`Cache::try_new`] when the capacity is too large.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CapacityError {
+    capacity: usize,
+    max: usize,
+}
+
+impl CapacityError {
+    /// Returns the capacity that was rejected.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the largest capacity that would have been accepted.
+    pub fn max(&self) -> usize {
+        self.max
+    }
+}
+
+impl fmt::Display for CapacityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "capacity {} exceeds the maximum of {}",
+            self.capacity, self.max
+        )
+    }
+}
+
+impl std::error::Error for CapacityError {}
+
+/// The clone keeps the entries, pins, stats, admission policy, adaptive
+/// capacity and occupancy histogram, but not the callbacks: the
+/// [`Cache::on_evict`] and [`Cache::on_resize`] callbacks, the eviction
+/// listeners, the latency recorder and the victim selector are left out.
+impl<K, V, S> Clone for Cache<K, V, S>
+where
+    K: std::cmp::Eq + std::hash::Hash + Clone,
+    V: Clone,
+    S: std::hash::BuildHasher + Clone,
+{
+    fn clone(&self) -> Self {
+        // Start unbounded, since pinned entries can leave the cache over
+        // capacity.
+        let mut cache = Self::with_hasher(usize::MAX, self.map.hasher().clone());
+
+        for (key, value) in self.iter().rev() {
+            cache.insert(key.clone(), value.clone());
+
+            if self.is_pinned(key) {
+                cache.pin(key);
+            }
+        }
+
+        cache.capacity = self.capacity;
+        cache.stats = self.stats;
+        cache.admission = self.admission.clone();
+        cache.adaptive = self.adaptive;
+        cache.occupancy = self.occupancy.clone();
+        cache
+    }
+}
+
+impl<K, V, S> Default for Cache<K, V, S>
+where
+    K: std::cmp::Eq + std::hash::Hash + Clone,
+    S: std::hash::BuildHasher + Default,
+{
+    /// Creates an empty cache with a capacity of [`DEFAULT_CAPACITY`].
+    fn default() -> Self {
+        Self::with_hasher(DEFAULT_CAPACITY, S::default())
+    }
+}
+
+impl<K, V, S> FromIterator<(K, V)> for Cache<K, V, S>
+where
+    K: std::cmp::Eq + std::hash::Hash + Clone,
+    S: std::hash::BuildHasher + Default,
+{
+    /// Creates a cache with a capacity equal to the number of pairs yielded by
+    /// the iterator. Pairs are inserted in order, so later duplicates overwrite
+    /// earlier ones and the last pair ends up the most recently used.
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let items: Vec<_> = iter.into_iter().collect();
+        let mut cache = Self::with_hasher(items.len(), S::default());
+        cache.extend(items);
+        cache
+    }
+}
+
+impl<K, V, S> Extend<(K, V)> for Cache<K, V, S>
+where
+    K: std::cmp::Eq + std::hash::Hash + Clone,
+    S: std::hash::BuildHasher,
+{
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (key, value) in iter {
+            self.insert(key, value);
+        }
+    }
+}
+
+/// Looks up a key like [`Cache::peek`], so that, unlike [`Cache::get`],
+/// `cache[&key]` does not change the recency order.
+///
+/// # Panics
+///
+/// Panics if the key is not present in the cache.
+impl<K, Q, V, S> std::ops::Index<&Q> for Cache<K, V, S>
+where
+    K: std::cmp::Eq + std::hash::Hash + Clone + std::borrow::Borrow<Q>,
+    Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    S: std::hash::BuildHasher,
+{
+    type Output = V;
+
+    fn index(&self, key: &Q) -> &V {
+        self.peek(key).expect("key not found in cache")
+    }
+}
+
+/// Two caches are equal if they have the same capacity and hold the same
+/// entries in the same recency order.
+impl<K, V, S> PartialEq for Cache<K, V, S>
+where
+    K: PartialEq,
+    V: PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.capacity == other.capacity && self.iter().eq(other.iter())
+    }
+}
+
+impl<K: Eq, V: Eq, S> Eq for Cache<K, V, S> {}
+
+/// Hashes the capacity and the entries in recency order, so that caches that
+/// are equal hash the same.
+impl<K, V, S> std::hash::Hash for Cache<K, V, S>
+where
+    K: std::hash::Hash,
+    V: std::hash::Hash,
+{
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.capacity.hash(state);
+        self.map.len().hash(state);
+
+        for (key, value) in self {
+            key.hash(state);
+            value.hash(state);
+        }
+    }
+}
+
+impl<K, V, S> fmt::Debug for Cache<K, V, S>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        struct Entries<'a, K, V, S>(&'a Cache<K, V, S>);
+
+        impl<K: fmt::Debug, V: fmt::Debug, S> fmt::Debug for Entries<'_, K, V, S> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.debug_map().entries(self.0.iter()).finish()
+            }
+        }
+
+        f.debug_struct("Cache")
+            .field("capacity", &self.capacity)
+            .field("len", &self.map.len())
+            .field("entries", &Entries(self))
+            .finish()
+    }
+}
+
+/// An iterator over the entries of a cache, from the most recently used to the
+/// least recently used.
+pub struct Iter<'a, K, V> {
+    head: Option<NonNull<Node<K, V>>>,
+    tail: Option<NonNull<Node<K, V>>>,
+    len: usize,
+    marker: PhantomData<&'a Node<K, V>>,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let node = unsafe { self.head?.as_ref() };
+        self.head = node.next;
+        self.len -= 1;
+        Some((&node.key, &node.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<K, V> DoubleEndedIterator for Iter<'_, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let node = unsafe { self.tail?.as_ref() };
+        self.tail = node.prev;
+        self.len -= 1;
+        Some((&node.key, &node.value))
+    }
+}
+
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+
+impl<'a, K, V, S> IntoIterator for &'a Cache<K, V, S> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// A mutable iterator over the entries of a cache, from the most recently used
+/// to the least recently used.
+pub struct IterMut<'a, K, V> {
+    head: Option<NonNull<Node<K, V>>>,
+    tail: Option<NonNull<Node<K, V>>>,
+    len: usize,
+    marker: PhantomData<&'a mut Node<K, V>>,
+}
+
+// Every node is visited once, through the raw pointer, so the mutable
+// references handed out never alias each other.
+
+impl<'a, K, V> Iterator for IterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let node = self.head?.as_ptr();
+        self.len -= 1;
+
+        unsafe {
+            self.head = (*node).next;
+            Some((&(*node).key, &mut (*node).value))
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<K, V> DoubleEndedIterator for IterMut<'_, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let node = self.tail?.as_ptr();
+        self.len -= 1;
+
+        unsafe {
+            self.tail = (*node).prev;
+            Some((&(*node).key, &mut (*node).value))
+        }
+    }
+}
+
+impl<K, V> ExactSizeIterator for IterMut<'_, K, V> {}
+
+impl<'a, K, V, S> IntoIterator for &'a mut Cache<K, V, S> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+/// A consuming iterator over the entries of a cache, from the most recently
+/// used to the least recently used.
+pub struct IntoIter<K, V> {
+    head: Option<NonNull<Node<K, V>>>,
+    tail: Option<NonNull<Node<K, V>>>,
+    len: usize,
+}
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let head = self.head?;
+        let node = unsafe { Box::from_raw(head.as_ptr()) };
+        self.head = node.next;
+
+        match node.next {
+            Some(next) => unsafe { (*next.as_ptr()).prev = None },
+            None => self.tail = None,
+        }
+
+        self.len -= 1;
+        Some((node.key, node.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<K, V> DoubleEndedIterator for IntoIter<K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let tail = self.tail?;
+        let node = unsafe { Box::from_raw(tail.as_ptr()) };
+        self.tail = node.prev;
+
+        match node.prev {
+            Some(prev) => unsafe { (*prev.as_ptr()).next = None },
+            None => self.head = None,
+        }
+
+        self.len -= 1;
+        Some((node.key, node.value))
+    }
+}
+
+impl<K, V> ExactSizeIterator for IntoIter<K, V> {}
+
+impl<K, V> Drop for IntoIter<K, V> {
+    fn drop(&mut self) {
+        for _ in self.by_ref() {}
+    }
+}
+
+impl<K, V, S> IntoIterator for Cache<K, V, S> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    fn into_iter(mut self) -> Self::IntoIter {
+        self.take_nodes()
+    }
+}
+
+/// A draining iterator over the entries of a cache, from the most recently
+/// used to the least recently used, returned by [`Cache::drain`].
+pub struct Drain<'a, K, V> {
+    iter: IntoIter<K, V>,
+    marker: PhantomData<&'a mut (K, V)>,
+}
+
+impl<K, V> Iterator for Drain<'_, K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<K, V> DoubleEndedIterator for Drain<'_, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.iter.next_back()
+    }
+}
+
+impl<K, V> ExactSizeIterator for Drain<'_, K, V> {}
+
+// SAFETY: The cache exclusively owns every node it points to, the pointers are
+// never shared outside of it, and mutation of the list requires `&mut self`.
+// It is therefore as thread-safe as owning the keys and values directly. The
+// eviction callback and listeners are `Send` and only ever reached through
+// `&mut self`. The victim selector is called from `&self` methods, so it is
+// also required to be `Sync`.
+unsafe impl<K: Send, V: Send, S: Send> Send for Cache<K, V, S> {}
+unsafe impl<K: Sync, V: Sync, S: Sync> Sync for Cache<K, V, S> {}
+
+// SAFETY: The iterator only hands out shared references to the nodes.
+unsafe impl<K: Sync, V: Sync> Send for Iter<'_, K, V> {}
+unsafe impl<K: Sync, V: Sync> Sync for Iter<'_, K, V> {}
+
+// SAFETY: The iterator hands out shared references to the keys and exclusive
+// references to the values, like `(&K, &mut V)`.
+unsafe impl<K: Sync, V: Send> Send for IterMut<'_, K, V> {}
+unsafe impl<K: Sync, V: Sync> Sync for IterMut<'_, K, V> {}
+
+// SAFETY: The iterator exclusively owns the nodes it has yet to yield.
+unsafe impl<K: Send, V: Send> Send for IntoIter<K, V> {}
+unsafe impl<K: Sync, V: Sync> Sync for IntoIter<K, V> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::BuildHasherDefault;
+    use std::rc::Rc;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_cache() {
+        let mut cache = Cache::new(3);
+
+        cache.insert(1, "aws".to_owned());
+        assert_eq!(cache.get(&1), Some(&"aws".to_owned()));
+
+        cache.insert(2, "gcp".to_owned());
+        cache.insert(3, "azure".to_owned());
+        assert_eq!(cache.get(&3), Some(&"azure".to_owned()));
+
+        cache.insert(4, "vmware".to_owned());
+        assert_eq!(cache.get(&2), Some(&"gcp".to_owned()));
+        assert_eq!(cache.get(&1), None);
+
+        cache.insert(5, "val".to_owned());
+        assert_eq!(cache.get(&5), Some(&"val".to_owned()));
+        assert_eq!(cache.get(&4), Some(&"vmware".to_owned()));
+        assert_eq!(cache.get(&3), None);
+        assert_eq!(cache.get(&2), Some(&"gcp".to_owned()));
+        assert_eq!(cache.get(&1), None);
+    }
+
+    #[test]
+    fn test_with_capacity() {
+        let mut cache = Cache::with_capacity(64);
+        assert_eq!(cache.capacity(), 64);
+        assert!(cache.map.capacity() >= 64);
+
+        cache.insert(1, "aws");
+        cache.reserve(100);
+        assert!(cache.map.capacity() >= 101);
+        assert_eq!(cache.capacity(), 64);
+
+        cache.shrink_to_fit();
+        assert!(cache.map.capacity() < 64);
+        assert_eq!(cache.get(&1), Some(&"aws"));
+    }
+
+    #[test]
+    fn test_try_new() {
+        assert!(Cache::<i32, i32>::try_new(MAX_CAPACITY).is_ok());
+
+        let error = Cache::<i32, i32>::try_new(usize::MAX).unwrap_err();
+        assert_eq!(error.capacity(), usize::MAX);
+        assert_eq!(error.max(), MAX_CAPACITY);
+
+        let error = Cache::<i32, i32>::try_new_with_limit(usize::MAX / 2, usize::MAX).unwrap_err();
+        assert!(error.max() < usize::MAX / 2);
+        assert_eq!(
+            Cache::<i32, i32>::try_new_with_limit(11, 10)
+                .unwrap_err()
+                .to_string(),
+            "capacity 11 exceeds the maximum of 10"
+        );
+    }
+
+    #[test]
+    fn test_with_hasher() {
+        let hasher = BuildHasherDefault::<DefaultHasher>::default();
+        let mut cache = Cache::with_hasher(2, hasher);
+
+        cache.insert(1, "aws".to_owned());
+        cache.insert(2, "gcp".to_owned());
+        cache.insert(3, "azure".to_owned());
+        assert_eq!(cache.get(&1), None);
+        assert_eq!(cache.get(&3), Some(&"azure".to_owned()));
+    }
+
+    #[test]
+    fn test_with_deterministic_hasher() {
+        let build = || {
+            let mut cache = Cache::with_deterministic_hasher(100);
+            cache.extend((0..100).map(|key| (key, key)));
+            cache
+        };
+
+        let (a, b) = (build(), build());
+        assert!(a.map.keys().eq(b.map.keys()));
+    }
+
+    #[test]
+    fn test_borrowed_key() {
+        let mut cache = Cache::new(2);
+
+        cache.insert("aws".to_owned(), 1);
+        cache.insert("gcp".to_owned(), 2);
+        assert!(cache.contains_key("aws"));
+        assert_eq!(cache.peek("gcp"), Some(&2));
+        assert_eq!(cache.get("aws"), Some(&1));
+        *cache.get_mut("gcp").unwrap() += 1;
+        assert_eq!(cache.remove("gcp"), Some(3));
+    }
+
+    #[test]
+    fn test_drop() {
+        let mut cache = Cache::new(2);
+
+        cache.insert("aws".to_owned(), "amazon".to_owned());
+        cache.insert("gcp".to_owned(), "google".to_owned());
+        assert_eq!(cache.get(&"aws".to_owned()), Some(&"amazon".to_owned()));
+
+        drop(cache);
+    }
+
+    struct DropCounter(Rc<Cell<usize>>);
+
+    impl Drop for DropCounter {
+        fn drop(&mut self) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[test]
+    fn test_eviction_drops_value() {
+        let drops = Rc::new(Cell::new(0));
+        let mut cache = Cache::new(2);
+
+        for i in 0..10 {
+            cache.insert(i, DropCounter(drops.clone()));
+        }
+        assert_eq!(drops.get(), 8);
+
+        drop(cache);
+        assert_eq!(drops.get(), 10);
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut cache = Cache::new(4);
+
+        cache.insert(1, "aws".to_owned());
+        cache.insert(2, "gcp".to_owned());
+        cache.insert(3, "azure".to_owned());
+        cache.insert(4, "vmware".to_owned());
+
+        // head
+        assert_eq!(cache.remove(&4), Some("vmware".to_owned()));
+        // tail
+        assert_eq!(cache.remove(&1), Some("aws".to_owned()));
+        // missing
+        assert_eq!(cache.remove(&1), None);
+        assert_eq!(cache.get(&4), None);
+
+        cache.insert(5, "val".to_owned());
+        cache.insert(6, "other".to_owned());
+        cache.insert(7, "more".to_owned());
+        assert_eq!(cache.get(&2), None);
+        assert_eq!(cache.get(&3), Some(&"azure".to_owned()));
+
+        // middle
+        assert_eq!(cache.remove(&6), Some("other".to_owned()));
+        assert_eq!(cache.remove(&5), Some("val".to_owned()));
+        assert_eq!(cache.remove(&7), Some("more".to_owned()));
+
+        // only
+        assert_eq!(cache.remove(&3), Some("azure".to_owned()));
+        assert!(cache.head.is_none());
+        assert!(cache.tail.is_none());
+
+        cache.insert(8, "fresh".to_owned());
+        assert_eq!(cache.get(&8), Some(&"fresh".to_owned()));
+    }
+
+    #[test]
+    fn test_access() {
+        let mut cache = Cache::new(2);
+
+        cache.insert(1, "aws");
+        cache.insert(2, "gcp");
+
+        let guard = cache.access(&1).unwrap();
+        assert_eq!(*guard, "aws");
+        assert_eq!(guard.key(), &1);
+        std::mem::forget(guard);
+        assert_eq!(cache.lru_key(), Some(&1));
+
+        let guard = cache.access(&1).unwrap();
+        drop(guard);
+        assert_eq!(cache.lru_key(), Some(&2));
+
+        assert!(cache.access(&3).is_none());
+        assert_eq!(cache.stats().hits, 2);
+        assert_eq!(cache.stats().misses, 1);
+    }
+
+    #[test]
+    fn test_split_off() {
+        let mut cache = Cache::new(4);
+
+        cache.insert(1, "aws");
+        cache.insert(2, "gcp");
+        cache.insert(3, "azure");
+        cache.insert(4, "vmware");
+        cache.pin(&2);
+
+        let even = cache.split_off(|key, _| key % 2 == 0);
+        assert_eq!(even.capacity(), 4);
+        assert_eq!(even.to_vec(), [(4, "vmware"), (2, "gcp")]);
+        assert!(even.is_pinned(&2));
+        assert_eq!(cache.to_vec(), [(3, "azure"), (1, "aws")]);
+
+        let none = cache.split_off(|_, _| false);
+        assert!(none.is_empty());
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn test_admission_policy() {
+        let cache = Cache::<i32, i32>::with_admission_policy(2, AdmissionPolicy::TinyLfu);
+        assert_eq!(cache.admission_policy(), AdmissionPolicy::TinyLfu);
+
+        let mut cache = Cache::with_deterministic_hasher(2);
+        cache.set_admission_policy(AdmissionPolicy::TinyLfu);
+
+        cache.insert(1, "aws");
+        cache.insert(2, "gcp");
+        cache.get(&1);
+        cache.get(&2);
+
+        // A one-hit wonder doesn't displace the hot entries.
+        assert_eq!(cache.insert(3, "azure"), Some((3, "azure")));
+        assert_eq!(cache.len(), 2);
+
+        for _ in 0..3 {
+            cache.get(&4);
+        }
+
+        assert_eq!(cache.insert(4, "vmware"), Some((1, "aws")));
+        assert_eq!(cache.insert(2, "google"), Some((2, "gcp")));
+
+        // Inserting through an entry always admits the key.
+        assert_eq!(cache.get_or_insert(6, "other"), &"other");
+        assert!(!cache.contains_key(&4));
+
+        cache.set_admission_policy(AdmissionPolicy::Always);
+        assert_eq!(cache.insert(5, "val"), Some((2, "google")));
+    }
+
+    #[test]
+    fn test_admission_policy_counts_every_lookup() {
+        let mut cache = Cache::with_deterministic_hasher(2);
+        cache.set_admission_policy(AdmissionPolicy::TinyLfu);
+
+        cache.insert(1, "aws");
+        cache.insert(2, "gcp");
+        cache.get(&1);
+        cache.get(&2);
+
+        cache.get_without_promote(&3);
+        cache.get_and_demote(&3);
+        assert!(cache.access(&3).is_none());
+        cache.get_many([&3]);
+        assert_eq!(cache.insert(3, "azure"), Some((1, "aws")));
+    }
+
+    #[test]
+    fn test_admission_policy_push() {
+        let mut cache = Cache::with_deterministic_hasher(2);
+        cache.set_admission_policy(AdmissionPolicy::TinyLfu);
+
+        cache.push(1, "aws");
+        cache.push(2, "gcp");
+        cache.get(&1);
+        cache.get(&2);
+
+        assert_eq!(cache.push(3, "azure"), Some((3, "azure")));
+        assert_eq!(cache.insert_lru(4, "vmware"), Some((4, "vmware")));
+        assert_eq!(cache.insert_many([(5, "val")]), [(5, "val")]);
+        assert!(!cache.last_insert_evicted());
+        assert_eq!(cache.keys().collect::<Vec<_>>(), [&2, &1]);
+    }
+
+    #[test]
+    fn test_get_or_insert_with_arg() {
+        let mut cache = Cache::new(2);
+        let mut loads = Vec::new();
+
+        let value = cache.get_or_insert_with_arg(1, &mut loads, |key, loads| {
+            loads.push(*key);
+            key * 10
+        });
+        assert_eq!(value, &10);
+
+        let value = cache.get_or_insert_with_arg(1, &mut loads, |_, _| unreachable!());
+        assert_eq!(value, &10);
+        assert_eq!(loads, [1]);
+
+        assert_eq!(cache.entry(2).or_insert_with_key(|key| key * 10), &20);
+    }
+
+    #[test]
+    fn test_recency_buckets() {
+        let mut cache = Cache::new(5);
+
+        for key in 1..=5 {
+            cache.insert(key, ());
+        }
+
+        assert_eq!(cache.recency_buckets(2), [vec![&5, &4, &3], vec![&2, &1]]);
+        assert_eq!(
+            cache.recency_buckets(7),
+            [
+                vec![&5],
+                vec![&4],
+                vec![&3],
+                vec![&2],
+                vec![&1],
+                vec![],
+                vec![]
+            ]
+        );
+        assert!(cache.recency_buckets(0).is_empty());
+    }
+
+    #[test]
+    fn test_key_set() {
+        let mut cache = Cache::new(3);
+
+        cache.insert(1, "aws".to_owned());
+        cache.insert(2, "gcp".to_owned());
+        cache.insert(3, "azure".to_owned());
+        cache.get(&1);
+
+        assert_eq!(cache.key_set(), [2, 3, 1]);
+    }
+
+    #[test]
+    fn test_eviction_causes() {
+        let causes = Arc::new(Mutex::new(Vec::new()));
+        let mut cache = Cache::new(2);
+
+        cache.register_eviction_listener({
+            let causes = causes.clone();
+            move |key, value, cause| causes.lock().unwrap().push((key, value, cause))
+        });
+
+        cache.insert(1, "aws");
+        cache.insert(2, "gcp");
+        cache.insert(1, "amazon");
+        cache.insert(3, "azure");
+        cache.remove(&1);
+        cache.insert(4, "vmware");
+        cache.resize(1);
+        cache.clear();
+        drop(cache);
+
+        assert_eq!(
+            *causes.lock().unwrap(),
+            [
+                (1, "aws", EvictionCause::Replaced),
+                (2, "gcp", EvictionCause::Capacity),
+                (1, "amazon", EvictionCause::Explicit),
+                (3, "azure", EvictionCause::Capacity),
+                (4, "vmware", EvictionCause::Explicit),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_insert_if_changed() {
+        let replaced = Arc::new(Mutex::new(Vec::new()));
+        let mut cache = Cache::new(2);
+
+        cache.register_eviction_listener({
+            let replaced = replaced.clone();
+            move |key, value, _| replaced.lock().unwrap().push((key, value))
+        });
+
+        assert!(cache.insert_if_changed(1, "aws"));
+        cache.insert(2, "gcp");
+
+        assert!(!cache.insert_if_changed(1, "aws"));
+        assert_eq!(cache.mru_key(), Some(&1));
+        assert!(replaced.lock().unwrap().is_empty());
+
+        assert!(cache.insert_if_changed(1, "amazon"));
+        assert_eq!(cache.peek(&1), Some(&"amazon"));
+        assert_eq!(*replaced.lock().unwrap(), [(1, "aws")]);
+
+        cache.pin(&1);
+        cache.pin(&2);
+        assert!(!cache.insert_if_changed(3, "azure"));
+        assert!(!cache.contains_key(&3));
+
+        let mut cache = Cache::new(0);
+        assert!(!cache.insert_if_changed(1, "aws"));
+    }
+
+    #[test]
+    fn test_neighbors() {
+        let mut cache = Cache::new(3);
+
+        cache.insert(1, "aws");
+        cache.insert(2, "gcp");
+        cache.insert(3, "azure");
+
+        assert_eq!(cache.neighbors(&3), Some((None, Some(&2))));
+        assert_eq!(cache.neighbors(&2), Some((Some(&3), Some(&1))));
+        assert_eq!(cache.neighbors(&1), Some((Some(&2), None)));
+        assert_eq!(cache.neighbors(&4), None);
+    }
+
+    #[test]
+    fn test_drain_filter() {
+        let mut cache = Cache::new(5);
+
+        for key in 1..=5 {
+            cache.insert(key, key * 10);
+        }
+
+        // The head, an interior entry and the tail.
+        let drained = cache.drain_filter(|key, _| [5, 3, 1].contains(key));
+        assert_eq!(drained, [(5, 50), (3, 30), (1, 10)]);
+        assert_eq!(cache.to_vec(), [(4, 40), (2, 20)]);
+        assert_eq!(cache.verify(), Ok(()));
+
+        assert!(cache.drain_filter(|_, _| false).is_empty());
+        assert_eq!(cache.drain_filter(|_, _| true).len(), 2);
+        assert!(cache.head.is_none());
+        assert!(cache.tail.is_none());
+    }
+
+    #[test]
+    fn test_remaining_capacity() {
+        let mut cache = Cache::new(2);
+        assert_eq!(cache.remaining_capacity(), 2);
+
+        cache.insert(1, "aws");
+        assert_eq!(cache.remaining_capacity(), 1);
+
+        cache.insert(2, "gcp");
+        cache.pin(&1);
+        cache.pin(&2);
+        cache.resize(1);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.remaining_capacity(), 0);
+    }
+
+    #[test]
+    fn test_get_owned() {
+        let mut cache = Cache::new(2);
+
+        cache.insert("aws".to_owned(), 1);
+        cache.insert("gcp".to_owned(), 2);
+
+        assert_eq!(cache.get_owned("aws".to_owned()), Some(&1));
+        assert_eq!(cache.get_owned("azure".to_owned()), None);
+        assert_eq!(cache.mru_key().map(String::as_str), Some("aws"));
+    }
+
+    #[test]
+    fn test_fill_unchecked() {
+        let mut cache = Cache::new(3);
+
+        cache.insert(1, "aws");
+        cache.fill_unchecked([(2, "gcp"), (1, "amazon"), (3, "azure")]);
+
+        assert_eq!(cache.to_vec(), [(3, "azure"), (1, "amazon"), (2, "gcp")]);
+        assert_eq!(cache.stats().insertions, 3);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "`fill_unchecked` exceeded the capacity of the cache")]
+    fn test_fill_unchecked_overflow() {
+        let mut cache = Cache::new(1);
+
+        cache.fill_unchecked([(1, "aws"), (2, "gcp")]);
+    }
+
+    #[test]
+    fn test_total_value_bytes() {
+        let mut cache = Cache::new(3);
+        assert_eq!(cache.total_value_bytes(String::len), 0);
+
+        cache.insert(1, "aws".to_owned());
+        cache.insert(2, "azure".to_owned());
+        assert_eq!(cache.total_value_bytes(String::len), 8);
+    }
+
+    #[test]
+    fn test_insert_unique() {
+        let mut cache = Cache::new(2);
+
+        assert_eq!(cache.insert_unique(1, "aws"), Ok(None));
+        assert_eq!(cache.insert_unique(2, "gcp"), Ok(None));
+        assert_eq!(cache.insert_unique(1, "amazon"), Err((1, "amazon")));
+        assert_eq!(cache.peek(&1), Some(&"aws"));
+        assert_eq!(cache.lru_key(), Some(&1));
+
+        assert_eq!(cache.insert_unique(3, "azure"), Ok(Some((1, "aws"))));
+        assert!(!cache.contains_key(&1));
+
+        let evicted = Arc::new(Mutex::new(Vec::new()));
+        cache.on_evict({
+            let evicted = evicted.clone();
+            move |key, value| evicted.lock().unwrap().push((key, value))
+        });
+        assert_eq!(cache.insert_unique(4, "oracle"), Ok(None));
+        assert_eq!(*evicted.lock().unwrap(), [(2, "gcp")]);
+
+        let mut cache = Cache::new(0);
+        assert_eq!(cache.insert_unique(1, "aws"), Err((1, "aws")));
+    }
+
+    #[test]
+    fn test_peek_or_insert_with() {
+        let mut cache = Cache::new(2);
+
+        cache.insert(1, "aws");
+        assert_eq!(cache.peek_or_insert_with(2, || "gcp"), &"gcp");
+        assert_eq!(cache.lru_key(), Some(&2));
+
+        cache.insert(3, "azure");
+        assert_eq!(cache.peek_or_insert_with(1, || unreachable!()), &"aws");
+        assert_eq!(cache.lru_key(), Some(&1));
+        assert_eq!(cache.peek_or_insert_with(4, || "vmware"), &"vmware");
+        assert_eq!(cache.keys().collect::<Vec<_>>(), [&3, &4]);
+    }
+
+    #[test]
+    fn test_swap_values() {
+        let mut cache = Cache::new(3);
+
+        cache.insert(1, "aws");
+        cache.insert(2, "gcp");
+        cache.insert(3, "azure");
+
+        assert!(cache.swap_values(&1, &3));
+        assert_eq!(cache.to_vec(), [(3, "aws"), (2, "gcp"), (1, "azure")]);
+        assert!(cache.swap_values(&2, &2));
+        assert_eq!(cache.peek(&2), Some(&"gcp"));
+        assert!(!cache.swap_values(&1, &4));
+        assert_eq!(cache.peek(&1), Some(&"azure"));
+    }
+
+    #[test]
+    fn test_from_slice() {
+        let cache = Cache::from_slice(3, &[(1, "aws"), (2, "gcp")]);
+        assert_eq!(cache.capacity(), 3);
+        assert_eq!(cache.to_vec(), [(2, "gcp"), (1, "aws")]);
+
+        let cache = Cache::from_slice(2, &[(1, "aws"), (2, "gcp"), (3, "azure")]);
+        assert_eq!(cache.to_vec(), [(3, "azure"), (2, "gcp")]);
+    }
+
+    #[test]
+    fn test_extremes() {
+        let mut cache = Cache::new(3);
+        assert_eq!(cache.extremes(), None);
+
+        cache.insert(1, "aws");
+        assert_eq!(cache.extremes(), Some((&1, &1)));
+
+        cache.insert(2, "gcp");
+        cache.insert(3, "azure");
+        assert_eq!(cache.extremes(), Some((&3, &1)));
+    }
+
+    #[test]
+    fn test_order_fingerprint() {
+        let mut cache = Cache::with_deterministic_hasher(3);
+        let mut other = Cache::with_deterministic_hasher(3);
+
+        for key in 1..=3 {
+            cache.insert(key, "aws");
+            other.insert(key, "gcp");
+        }
+
+        assert_eq!(cache.order_fingerprint().len(), 3);
+        assert_eq!(cache.order_fingerprint(), other.order_fingerprint());
+
+        other.get(&1);
+        assert_ne!(cache.order_fingerprint(), other.order_fingerprint());
+    }
+
+    #[test]
+    fn test_last_insert_evicted() {
+        let mut cache = Cache::new(2);
+        assert!(!cache.last_insert_evicted());
+
+        cache.on_evict(|_, _| {});
+        cache.insert(1, "aws");
+        cache.insert(2, "gcp");
+        assert!(!cache.last_insert_evicted());
+
+        assert_eq!(cache.insert(3, "azure"), None);
+        assert!(cache.last_insert_evicted());
+
+        cache.insert(3, "microsoft");
+        assert!(!cache.last_insert_evicted());
+    }
+
+    #[test]
+    fn test_generation() {
+        let mut cache = Cache::new(2);
+        let initial = cache.generation();
+
+        cache.insert(1, "aws");
+        let inserted = cache.generation();
+        assert_ne!(inserted, initial);
+
+        cache.get(&1);
+        cache.peek(&1);
+        cache.promote(&1);
+        assert_eq!(cache.generation(), inserted);
+
+        cache.insert(2, "gcp");
+        cache.insert(3, "azure");
+        let evicted = cache.generation();
+        assert_ne!(evicted, inserted);
+
+        cache.remove(&2);
+        assert_ne!(cache.generation(), evicted);
+        let removed = cache.generation();
+
+        cache.clear();
+        assert_ne!(cache.generation(), removed);
+
+        let mut cache = Cache::new(0);
+        let initial = cache.generation();
+        assert_eq!(cache.insert(1, "aws"), Some((1, "aws")));
+        assert_eq!(cache.generation(), initial);
+
+        let mut cache = Cache::new(1);
+        cache.insert(1, "aws");
+        cache.pin(&1);
+        let pinned = cache.generation();
+        assert_eq!(cache.insert(2, "gcp"), Some((2, "gcp")));
+        assert_eq!(cache.generation(), pinned);
+    }
+
+    #[test]
+    fn test_insert_if() {
+        let mut cache = Cache::new(2);
+
+        assert!(cache.insert_if(1, "aws", |_, value| !value.is_empty()));
+        cache.insert(2, "gcp");
+        assert!(!cache.insert_if(3, "", |_, value| !value.is_empty()));
+        assert!(!cache.contains_key(&3));
+
+        assert!(!cache.insert_if(1, "", |_, value| !value.is_empty()));
+        assert_eq!(cache.peek(&1), Some(&"aws"));
+        assert_eq!(cache.lru_key(), Some(&1));
+
+        cache.pin(&1);
+        cache.pin(&2);
+        assert!(!cache.insert_if(3, "azure", |_, _| true));
+        assert!(!cache.contains_key(&3));
+
+        let mut cache = Cache::new(0);
+        assert!(!cache.insert_if(1, "aws", |_, _| true));
+    }
+
+    #[test]
+    fn test_update_in_place() {
+        let mut cache = Cache::new(3);
+
+        cache.insert(1, "aws");
+        cache.insert(2, "gcp");
+        cache.insert(3, "azure");
+
+        assert_eq!(cache.update_in_place(&2, "google"), Some("gcp"));
+        assert_eq!(cache.to_vec(), [(3, "azure"), (2, "google"), (1, "aws")]);
+        assert_eq!(cache.update_in_place(&4, "vmware"), None);
+        assert_eq!(cache.len(), 3);
+    }
+
+    #[test]
+    fn test_evict_to() {
+        let mut cache = Cache::new(3);
+
+        cache.insert(1, "aws");
+        cache.insert(2, "gcp");
+        cache.insert(3, "azure");
+
+        assert!(cache.evict_to(5).is_empty());
+        assert_eq!(cache.evict_to(1), [(1, "aws"), (2, "gcp")]);
+        assert_eq!(cache.capacity(), 3);
+
+        cache.insert(4, "vmware");
+        assert_eq!(cache.evict_to(0), [(3, "azure"), (4, "vmware")]);
+        assert!(cache.is_empty());
+        assert!(cache.tail.is_none());
+    }
+
+    #[test]
+    fn test_get_mut_or_insert() {
+        let mut cache = Cache::new(2);
+
+        let (value, occupancy) = cache.get_mut_or_insert(1, || 10);
+        assert_eq!(occupancy, Occupancy::Inserted);
+        *value += 1;
+
+        cache.insert(2, 20);
+        let (value, occupancy) = cache.get_mut_or_insert(1, || unreachable!());
+        assert_eq!((*value, occupancy), (11, Occupancy::Hit));
+        assert_eq!(cache.mru_key(), Some(&1));
+    }
+
+    #[test]
+    fn test_adaptive_capacity() {
+        let mut cache = Cache::with_adaptive_capacity(4, 6, 0.5);
+
+        for key in 0..ADAPTIVE_WINDOW {
+            cache.get(&key);
+        }
+
+        cache.insert(0, ());
+        assert_eq!(cache.capacity(), 5);
+
+        for _ in 0..2 {
+            for _ in 0..ADAPTIVE_WINDOW {
+                cache.get(&1);
+            }
+
+            cache.insert(0, ());
+            assert_eq!(cache.capacity(), 6);
+        }
+
+        for _ in 0..ADAPTIVE_WINDOW {
+            cache.get(&0);
+        }
+
+        cache.insert(1, ());
+        assert_eq!(cache.capacity(), 5);
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn test_adaptive_capacity_reset_stats() {
+        let mut cache = Cache::with_adaptive_capacity(4, 6, 0.5);
+
+        cache.insert(0, ());
+        for _ in 0..2 * ADAPTIVE_WINDOW {
+            cache.get(&0);
+        }
+
+        cache.insert(1, ());
+        assert_eq!(cache.capacity(), 4);
+
+        cache.reset_stats();
+        cache.insert(2, ());
+        assert_eq!(cache.capacity(), 4);
+
+        for _ in 0..ADAPTIVE_WINDOW {
+            cache.get(&3);
+        }
+
+        cache.insert(3, ());
+        assert_eq!(cache.capacity(), 5);
+    }
+
+    #[test]
+    fn test_take() {
+        let mut cache = Cache::new(2);
+
+        cache.insert("aws".to_owned(), 1);
+        cache.insert("gcp".to_owned(), 2);
+
+        assert_eq!(cache.take("aws"), Some(1));
+        assert_eq!(cache.take("aws"), None);
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_len() {
+        let mut cache = Cache::new(2);
+        assert!(cache.is_empty());
+        assert_eq!(cache.capacity(), 2);
+
+        cache.insert(1, "aws".to_owned());
+        cache.insert(1, "amazon".to_owned());
+        assert_eq!(cache.len(), 1);
+
+        cache.insert(2, "gcp".to_owned());
+        cache.insert(3, "azure".to_owned());
+        assert_eq!(cache.len(), 2);
+
+        cache.remove(&3);
+        assert_eq!(cache.len(), 1);
+
+        cache.remove(&2);
+        assert_eq!(cache.len(), 0);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_compact() {
+        let mut cache = Cache::new(100);
+
+        for key in 0..100 {
+            cache.insert(key, key.to_string());
+        }
+
+        cache.get(&50);
+        cache.retain(|key, _| key % 10 == 0);
+        let entries = cache.to_vec();
+        let map_capacity = cache.map.capacity();
+
+        cache.compact();
+        assert_eq!(cache.to_vec(), entries);
+        assert_eq!(cache.iter().rev().count(), 10);
+        assert!(cache.map.capacity() < map_capacity);
+        assert!(cache.verify().is_ok());
+
+        for (key, value) in &entries {
+            assert_eq!(cache.peek(key), Some(value));
+        }
+    }
+
+    #[test]
+    fn test_occupancy_histogram() {
+        let mut cache = Cache::new(4);
+        assert_eq!(cache.occupancy_histogram(), None);
+
+        cache.insert(0, 0);
+        cache.enable_occupancy_histogram(2);
+
+        for key in 1..6 {
+            cache.insert(key, key);
+        }
+
+        cache.remove(&5);
+        cache.remove(&4);
+        cache.remove(&3);
+        assert_eq!(cache.sample_occupancy(), 1);
+        assert_eq!(cache.occupancy_histogram(), Some(&[1, 7][..]));
+
+        cache.disable_occupancy_histogram();
+        cache.insert(1, 1);
+        assert_eq!(cache.occupancy_histogram(), None);
+    }
+
+    #[test]
+    fn test_load_factor() {
+        let mut cache = Cache::new(4);
+        assert_eq!(cache.load_factor(), 0.0);
+
+        cache.insert(1, "aws");
+        assert_eq!(cache.load_factor(), 0.25);
+
+        cache.resize(1);
+        assert_eq!(cache.load_factor(), 1.0);
+
+        cache.resize(0);
+        assert_eq!(cache.load_factor(), 0.0);
+    }
+
+    #[test]
+    fn test_resize() {
+        let mut cache = Cache::new(2);
+
+        cache.insert(1, "aws".to_owned());
+        cache.insert(2, "gcp".to_owned());
+        cache.resize(3);
+        cache.insert(3, "azure".to_owned());
+        assert_eq!(cache.len(), 3);
+        assert_eq!(cache.capacity(), 3);
+
+        cache.get(&1);
+        cache.resize(1);
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.peek(&1), Some(&"aws".to_owned()));
+
+        cache.resize(0);
+        assert!(cache.is_empty());
+        assert!(cache.head.is_none());
+        assert!(cache.tail.is_none());
+    }
+
+    #[test]
+    fn test_victim_selector() {
+        let candidates = Arc::new(Mutex::new(Vec::new()));
+        let mut cache = Cache::new(4).with_victim_selector({
+            let candidates = candidates.clone();
+            move |entries: &[(&i32, &i32)]| {
+                *candidates.lock().unwrap() = entries.iter().map(|(&key, _)| key).collect();
+                (0..entries.len()).min_by_key(|&i| entries[i].1).unwrap()
+            }
+        });
+
+        for (key, value) in [(1, 30), (2, 10), (3, 40), (4, 20)] {
+            cache.insert(key, value);
+        }
+
+        cache.pin(&2);
+        assert_eq!(cache.insert(5, 50), Some((4, 20)));
+        assert_eq!(*candidates.lock().unwrap(), [1, 3, 4]);
+        assert!(cache.contains_key(&2));
+
+        cache.resize(3);
+        assert!(!cache.contains_key(&1));
+    }
+
+    #[test]
+    fn test_reinit() {
+        let drops = Rc::new(Cell::new(0));
+        let mut cache = Cache::new(3);
+
+        cache.insert(1, DropCounter(drops.clone()));
+        cache.insert(2, DropCounter(drops.clone()));
+        cache.reinit(1);
+        assert_eq!(drops.get(), 2);
+        assert!(cache.is_empty());
+        assert_eq!(cache.capacity(), 1);
+
+        cache.insert(3, DropCounter(drops.clone()));
+        cache.insert(4, DropCounter(drops.clone()));
+        assert_eq!(drops.get(), 3);
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_keep_hottest() {
+        let mut cache = Cache::new(4);
+
+        for key in 1..=4 {
+            cache.insert(key, key * 10);
+        }
+
+        assert!(cache.keep_hottest(5).is_empty());
+        assert_eq!(cache.capacity(), 5);
+
+        cache.get(&1);
+        assert_eq!(cache.keep_hottest(1), [(2, 20), (3, 30), (4, 40)]);
+        assert_eq!(cache.to_vec(), [(1, 10)]);
+        assert_eq!(cache.capacity(), 1);
+
+        assert_eq!(cache.keep_hottest(0), [(1, 10)]);
+        assert!(cache.is_empty());
+        assert_eq!(cache.capacity(), 0);
+    }
+
+    #[test]
+    fn test_contains_key() {
+        let mut cache = Cache::new(2);
+
+        cache.insert(1, "aws".to_owned());
+        cache.insert(2, "gcp".to_owned());
+        assert!(cache.contains_key(&1));
+        assert!(!cache.contains_key(&3));
+
+        cache.insert(3, "azure".to_owned());
+        assert!(!cache.contains_key(&1));
+        assert!(cache.contains_key(&2));
+        assert!(cache.contains_key(&3));
+    }
+
+    #[test]
+    fn test_peek() {
+        let mut cache = Cache::new(2);
+
+        cache.insert(1, "aws".to_owned());
+        cache.insert(2, "gcp".to_owned());
+        assert_eq!(cache.peek(&1), Some(&"aws".to_owned()));
+        assert_eq!(cache.peek(&3), None);
+
+        cache.insert(3, "azure".to_owned());
+        assert_eq!(cache.peek(&1), None);
+        assert_eq!(cache.peek(&2), Some(&"gcp".to_owned()));
+    }
+
+    #[test]
+    fn test_peek_mut() {
+        let mut cache = Cache::new(2);
+
+        cache.insert(1, "aws".to_owned());
+        cache.insert(2, "gcp".to_owned());
+        cache.peek_mut(&1).unwrap().push_str("-east");
+        assert_eq!(cache.peek_mut(&3), None);
+
+        cache.insert(3, "azure".to_owned());
+        assert_eq!(cache.peek(&1), None);
+        assert_eq!(cache.stats().hits, 0);
+    }
+
+    #[test]
+    fn test_try_get() {
+        let mut cache = Cache::new(2);
+
+        cache.insert(1, "aws");
+        cache.insert(2, "gcp");
+        assert_eq!(cache.try_get(&1), Ok(&"aws"));
+        assert_eq!(cache.try_get(&3), Err(CacheMiss));
+        assert_eq!(CacheMiss.to_string(), "key not found in cache");
+
+        cache.insert(3, "azure");
+        assert_eq!(cache.try_get(&2), Err(CacheMiss));
+    }
+
+    #[test]
+    fn test_get_mut() {
+        let mut cache = Cache::new(2);
+
+        cache.insert(1, 10);
+        cache.insert(2, 20);
+        *cache.get_mut(&1).unwrap() += 1;
+        assert_eq!(cache.get_mut(&3), None);
+
+        cache.insert(3, 30);
+        assert_eq!(cache.get(&1), Some(&11));
+        assert_eq!(cache.get(&2), None);
+    }
+
+    #[test]
+    fn test_get_with_rank() {
+        let mut cache = Cache::new(3);
+
+        cache.insert(1, "aws");
+        cache.insert(2, "gcp");
+        cache.insert(3, "azure");
+        assert_eq!(cache.get_with_rank(&1), Some((&"aws", 2)));
+        assert_eq!(cache.get_with_rank(&1), Some((&"aws", 0)));
+        assert_eq!(cache.get_with_rank(&3), Some((&"azure", 1)));
+        assert_eq!(cache.get_with_rank(&4), None);
+    }
+
+    #[test]
+    fn test_replace_with_rank() {
+        let mut cache = Cache::new(3);
+
+        cache.insert(1, "aws");
+        cache.insert(2, "gcp");
+        assert_eq!(cache.replace_with_rank(1, "amazon"), Some(("aws", 1)));
+        assert_eq!(cache.mru_key(), Some(&1));
+        assert_eq!(cache.replace_with_rank(3, "azure"), None);
+        assert_eq!(cache.replace_with_rank(3, "microsoft"), Some(("azure", 0)));
+        assert_eq!(
+            cache.to_vec(),
+            [(3, "microsoft"), (1, "amazon"), (2, "gcp")]
+        );
+    }
+
+    #[test]
+    fn test_key_at_rank() {
+        let mut cache = Cache::new(3);
+
+        cache.insert(1, "aws");
+        cache.insert(2, "gcp");
+        cache.insert(3, "azure");
+        assert_eq!(cache.key_at_rank(0), Some(&3));
+        assert_eq!(cache.key_at_rank(2), Some(&1));
+        assert_eq!(cache.key_at_rank(3), None);
+        assert_eq!(cache.lru_key(), Some(&1));
+    }
+
+    #[test]
+    fn test_get_and_demote() {
+        let mut cache = Cache::new(3);
+
+        cache.insert(1, "aws");
+        assert_eq!(cache.get_and_demote(&1), Some(&"aws"));
+        assert_eq!(cache.extremes(), Some((&1, &1)));
+
+        cache.insert(2, "gcp");
+        cache.insert(3, "azure");
+        assert_eq!(cache.get_and_demote(&3), Some(&"azure"));
+        assert_eq!(cache.keys().collect::<Vec<_>>(), [&2, &1, &3]);
+        assert_eq!(cache.get_and_demote(&4), None);
+        assert_eq!(cache.insert(4, "vmware"), Some((3, "azure")));
+    }
+
+    #[test]
+    fn test_get_without_promote() {
+        let mut cache = Cache::new(2);
+
+        cache.insert(1, "aws");
+        cache.insert(2, "gcp");
+        assert_eq!(cache.get_without_promote(&1), Some(&"aws"));
+        assert_eq!(cache.get_without_promote(&3), None);
+        assert_eq!(cache.lru_key(), Some(&1));
+        assert_eq!(cache.stats().hits, 1);
+        assert_eq!(cache.stats().misses, 1);
+    }
+
+    #[test]
+    fn test_get_many() {
+        let mut cache = Cache::new(3);
+
+        cache.insert(1, "aws");
+        cache.insert(2, "gcp");
+        cache.insert(3, "azure");
+
+        assert_eq!(
+            cache.get_many([&2, &4, &1, &2]),
+            [Some(&"gcp"), None, Some(&"aws"), Some(&"gcp")]
+        );
+        assert_eq!(cache.keys().copied().collect::<Vec<_>>(), [2, 1, 3]);
+        assert_eq!(cache.stats().hits, 3);
+        assert_eq!(cache.stats().misses, 1);
+    }
+
+    #[test]
+    fn test_get_many_mut() {
+        let mut cache = Cache::new(3);
+
+        cache.insert(1, 10);
+        cache.insert(2, 20);
+        cache.insert(3, 30);
+
+        let [a, b] = cache.get_many_mut([&1, &2]).unwrap();
+        std::mem::swap(a, b);
+        assert_eq!(cache.keys().copied().collect::<Vec<_>>(), [2, 1, 3]);
+        assert_eq!(cache.peek(&1), Some(&20));
+
+        assert!(cache.get_many_mut([&1, &1]).is_none());
+        assert!(cache.get_many_mut([&3, &4]).is_none());
+        assert_eq!(cache.peek_lru(), Some((&3, &30)));
+    }
+
+    #[test]
+    fn test_clear() {
+        let drops = Rc::new(Cell::new(0));
+        let mut cache = Cache::new(3);
+
+        cache.insert(1, DropCounter(drops.clone()));
+        cache.insert(2, DropCounter(drops.clone()));
+        cache.insert(3, DropCounter(drops.clone()));
+        cache.clear();
+        assert_eq!(drops.get(), 3);
+        assert_eq!(cache.len(), 0);
+        assert!(cache.get(&1).is_none());
+
+        cache.insert(4, DropCounter(drops.clone()));
+        assert!(cache.get(&4).is_some());
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_insert_returns_displaced() {
+        let mut cache = Cache::new(2);
+
+        assert_eq!(cache.insert(1, "aws".to_owned()), None);
+        assert_eq!(cache.insert(2, "gcp".to_owned()), None);
+        assert_eq!(
+            cache.insert(1, "amazon".to_owned()),
+            Some((1, "aws".to_owned()))
+        );
+        assert_eq!(
+            cache.insert(3, "azure".to_owned()),
+            Some((2, "gcp".to_owned()))
+        );
+    }
+
+    #[test]
+    fn test_verify() {
+        let mut cache = Cache::new(3);
+        assert_eq!(cache.verify(), Ok(()));
+
+        cache.insert(1, "aws");
+        cache.insert(2, "gcp");
+        cache.insert(3, "azure");
+        assert_eq!(cache.verify(), Ok(()));
+
+        let head = cache.head.take();
+        assert_eq!(
+            cache.verify(),
+            Err(CacheError::LengthMismatch { map: 3, list: 0 })
+        );
+        cache.head = head;
+
+        let middle = unsafe { cache.head.unwrap().as_ref().next.unwrap() };
+        unsafe { (*middle.as_ptr()).prev = None };
+        assert_eq!(cache.verify(), Err(CacheError::BrokenBackwardLink));
+        unsafe { (*middle.as_ptr()).prev = head };
+
+        let tail = cache.tail;
+        cache.tail = cache.head;
+        assert_eq!(cache.verify(), Err(CacheError::BrokenForwardLink));
+        cache.tail = tail;
+
+        let node = cache.map.remove(&2).unwrap();
+        assert_eq!(cache.verify(), Err(CacheError::KeyMismatch));
+        cache.map.insert(2, node);
+
+        unsafe { (*cache.tail.unwrap().as_ptr()).next = cache.head };
+        assert_eq!(
+            cache.verify(),
+            Err(CacheError::LengthMismatch { map: 3, list: 4 })
+        );
+        unsafe { (*cache.tail.unwrap().as_ptr()).next = None };
+        assert_eq!(cache.verify(), Ok(()));
+    }
+
+    #[test]
+    fn test_capacity_one() {
+        let mut cache = Cache::new(1);
+
+        for key in 0..10 {
+            let evicted = (key > 0).then(|| (key - 1, (key - 1) * 10));
+            assert_eq!(cache.insert(key, key * 10), evicted);
+            assert!(cache.head.is_some());
+            assert_eq!(cache.head, cache.tail);
+            assert_eq!(cache.get(&key), Some(&(key * 10)));
+            assert_eq!(cache.get(&(key - 1)), None);
+            assert_eq!(cache.len(), 1);
+        }
+
+        assert_eq!(cache.insert(9, 0), Some((9, 90)));
+        assert_eq!(cache.head, cache.tail);
+
+        assert_eq!(cache.remove(&9), Some(0));
+        assert_eq!(cache.head, None);
+        assert_eq!(cache.tail, None);
+
+        cache.insert(1, 1);
+        assert_eq!(cache.pop_lru(), Some((1, 1)));
+        assert!(cache.head.is_none() && cache.tail.is_none());
+        assert_eq!(cache.get_or_insert(2, 2), &2);
+        assert_eq!(cache.head, cache.tail);
+    }
+
+    #[test]
+    fn test_get_or_insert_with() {
+        let mut cache = Cache::new(2);
+
+        cache.insert(1, "aws".to_owned());
+        cache.insert(2, "gcp".to_owned());
+        assert_eq!(
+            cache.get_or_insert_with(1, || unreachable!()),
+            &"aws".to_owned()
+        );
+        assert_eq!(
+            cache.get_or_insert_with(3, || "azure".to_owned()),
+            &"azure".to_owned()
+        );
+        assert_eq!(cache.peek(&2), None);
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn test_latency_recorder() {
+        // Moves on by a millisecond every time it is read.
+        struct SteppingClock(Mutex<std::time::Instant>);
+
+        impl Clock for SteppingClock {
+            fn now(&self) -> std::time::Instant {
+                let mut now = self.0.lock().unwrap();
+                *now += Duration::from_millis(1);
+                *now
+            }
+        }
+
+        let latencies = Arc::new(Mutex::new(Vec::new()));
+        let clock = SteppingClock(Mutex::new(std::time::Instant::now()));
+        let mut cache = Cache::new(2).with_latency_recorder_and_clock(
+            {
+                let latencies = latencies.clone();
+                move |latency| latencies.lock().unwrap().push(latency)
+            },
+            clock,
+        );
+
+        cache.insert(1, "aws");
+        cache.get_or_insert_with(1, || unreachable!());
+        assert!(latencies.lock().unwrap().is_empty());
+
+        assert_eq!(cache.get_or_insert_with(2, || "gcp"), &"gcp");
+        assert_eq!(*latencies.lock().unwrap(), [Duration::from_millis(1)]);
+
+        let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            cache.get_or_insert_with(3, || panic!("failed to compute"));
+        }));
+        assert!(panicked.is_err());
+        assert_eq!(cache.len(), 2);
+
+        cache.get_or_insert_with(3, || "azure");
+        assert_eq!(latencies.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_get_or_insert_with_mut() {
+        let mut cache = Cache::new(2);
+
+        cache.get_or_insert_with_mut(1, Vec::new).push("aws");
+        cache
+            .get_or_insert_with_mut(1, || unreachable!())
+            .push("amazon");
+        cache.insert(2, vec!["gcp"]);
+        cache.get_or_insert_with_mut(3, Vec::new).push("azure");
+
+        assert_eq!(cache.peek(&1), None);
+        assert_eq!(cache.peek(&3), Some(&vec!["azure"]));
+
+        cache
+            .get_or_insert_with_mut(2, || unreachable!())
+            .push("google");
+        assert_eq!(cache.peek(&2), Some(&vec!["gcp", "google"]));
+    }
+
+    #[test]
+    fn test_get_or_insert() {
+        let drops = Rc::new(Cell::new(0));
+        let mut cache = Cache::new(2);
+
+        cache.get_or_insert(1, DropCounter(drops.clone()));
+        assert_eq!(drops.get(), 0);
+
+        cache.insert(2, DropCounter(drops.clone()));
+        cache.get_or_insert(1, DropCounter(drops.clone()));
+        assert_eq!(drops.get(), 1);
+        assert_eq!(cache.mru_key(), Some(&1));
+    }
+
+    #[test]
+    fn test_increment() {
+        let mut cache = Cache::new(2);
+
+        assert_eq!(cache.increment("aws", 2), &2);
+        assert_eq!(cache.increment("gcp", 1), &1);
+        assert_eq!(cache.increment("aws", 3), &5);
+        assert_eq!(cache.mru_key(), Some(&"aws"));
+
+        assert_eq!(cache.increment("azure", 1), &1);
+        assert_eq!(cache.peek(&"gcp"), None);
+        assert_eq!(cache.peek(&"aws"), Some(&5));
+    }
+
+    #[test]
+    fn test_get_or_insert_with_status() {
+        let mut cache = Cache::new(2);
+
+        assert_eq!(cache.get_or_insert_with_status(1, || "aws"), (&"aws", true));
+        assert_eq!(
+            cache.get_or_insert_with_status(1, || unreachable!()),
+            (&"aws", false)
+        );
+    }
+
+    #[test]
+    fn test_get_or_insert_ref() {
+        let mut cache: Cache<String, usize> = Cache::new(2);
+
+        assert_eq!(
+            cache.get_or_insert_ref("aws", || "aws".to_owned(), || 1),
+            &1
+        );
+        assert_eq!(
+            cache.get_or_insert_ref("aws", || unreachable!(), || unreachable!()),
+            &1
+        );
+        assert_eq!(cache.stats().hits, 1);
+        assert_eq!(cache.stats().misses, 1);
+    }
+
+    #[test]
+    fn test_get_or_try_insert_with() {
+        let mut cache = Cache::new(2);
+
+        assert_eq!(
+            cache.get_or_try_insert_with(1, || Err("unreachable host")),
+            Err::<&&str, _>("unreachable host")
+        );
+        assert!(!cache.contains_key(&1));
+
+        assert_eq!(
+            cache.get_or_try_insert_with(1, || Ok::<_, ()>("aws")),
+            Ok(&"aws")
+        );
+        assert_eq!(
+            cache.get_or_try_insert_with(1, || -> Result<_, ()> { unreachable!() }),
+            Ok(&"aws")
+        );
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_zero_capacity() {
+        let mut cache = Cache::new(0);
+
+        assert_eq!(
+            cache.insert(1, "aws".to_owned()),
+            Some((1, "aws".to_owned()))
+        );
+        assert!(cache.is_empty());
+        assert_eq!(cache.get(&1), None);
+    }
+
+    #[test]
+    fn test_entry() {
+        let mut cache = Cache::new(2);
+
+        *cache.entry("aws").or_insert(0) += 1;
+        cache.entry("gcp").and_modify(|n| *n += 1).or_insert(10);
+        cache.entry("aws").and_modify(|n| *n += 1).or_insert(10);
+        assert_eq!(cache.entry("aws").key(), &"aws");
+        assert_eq!(cache.keys().collect::<Vec<_>>(), [&"aws", &"gcp"]);
+
+        *cache.entry("azure").or_insert_with(|| 100) += 1;
+        assert_eq!(cache.peek("aws"), Some(&2));
+        assert_eq!(cache.peek("gcp"), None);
+        assert_eq!(cache.peek("azure"), Some(&101));
+    }
+
+    #[test]
+    fn test_stats() {
+        let mut cache = Cache::new(2);
+
+        cache.insert(1, "aws");
+        cache.insert(2, "gcp");
+        cache.insert(2, "google");
+        cache.insert(3, "azure");
+        cache.get(&1);
+        cache.get(&2);
+        cache.entry(3).or_insert("microsoft");
+        cache.entry(4).or_insert("vmware");
+
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                hits: 2,
+                misses: 2,
+                evictions: 2,
+                insertions: 4,
+            }
+        );
+
+        cache.reset_stats();
+        assert_eq!(cache.stats(), CacheStats::default());
+    }
+
+    #[test]
+    fn test_on_evict() {
+        let evicted = Arc::new(Mutex::new(Vec::new()));
+        let mut cache = Cache::new(2);
+
+        cache.on_evict({
+            let evicted = evicted.clone();
+            move |key, value| evicted.lock().unwrap().push((key, value))
+        });
+        cache.insert(1, "aws");
+        cache.insert(2, "gcp");
+        cache.insert(2, "google");
+        assert!(evicted.lock().unwrap().is_empty());
+
+        assert_eq!(cache.insert(3, "azure"), None);
+        assert_eq!(cache.insert(4, "vmware"), None);
+        assert_eq!(*evicted.lock().unwrap(), [(1, "aws"), (2, "google")]);
+    }
+
+    #[test]
+    fn test_on_resize() {
+        let resizes = Arc::new(Mutex::new(Vec::new()));
+        let mut cache = Cache::<_, ()>::with_adaptive_capacity(2, 4, 0.5);
+
+        cache.on_resize({
+            let resizes = resizes.clone();
+            move |old, new| resizes.lock().unwrap().push((old, new))
+        });
+        cache.resize(2);
+        assert!(resizes.lock().unwrap().is_empty());
+
+        cache.resize(3);
+
+        for _ in 0..ADAPTIVE_WINDOW {
+            cache.get(&1);
+        }
+
+        cache.insert(1, ());
+        assert_eq!(*resizes.lock().unwrap(), [(2, 3), (3, 4)]);
+    }
+
+    #[test]
+    fn test_retain() {
+        let mut cache = Cache::new(5);
+
+        for i in 1..=5 {
+            cache.insert(i, i * 10);
+        }
+
+        cache.retain(|key, value| {
+            *value += 1;
+            key % 2 == 0
+        });
+        assert_eq!(cache.iter().collect::<Vec<_>>(), [(&4, &41), (&2, &21)]);
+        assert_eq!(cache.len(), 2);
+
+        cache.retain(|key, _| *key != 4);
+        assert_eq!(cache.peek_lru(), Some((&2, &21)));
+        assert_eq!(cache.iter().next_back(), Some((&2, &21)));
+
+        cache.retain(|_, _| false);
+        assert!(cache.is_empty());
+        assert!(cache.head.is_none());
+        assert!(cache.tail.is_none());
+    }
+
+    #[test]
+    fn test_eviction_listeners() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut cache = Cache::new(1);
+
+        let first = cache.register_eviction_listener({
+            let events = events.clone();
+            move |key, value, _| events.lock().unwrap().push(("first", key, value))
+        });
+        cache.register_eviction_listener({
+            let events = events.clone();
+            move |key, value, _| events.lock().unwrap().push(("second", key, value))
+        });
+
+        cache.insert(1, "aws");
+        assert_eq!(cache.insert(2, "gcp"), Some((1, "aws")));
+        assert_eq!(
+            *events.lock().unwrap(),
+            [("first", 1, "aws"), ("second", 1, "aws")]
+        );
+
+        assert!(cache.remove_eviction_listener(first));
+        assert!(!cache.remove_eviction_listener(first));
+        cache.push(3, "azure");
+        assert_eq!(events.lock().unwrap().last(), Some(&("second", 2, "gcp")));
+        assert_eq!(events.lock().unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_push() {
+        let mut cache = Cache::new(2);
+        cache.on_evict(|_, _| unreachable!());
+
+        assert_eq!(cache.push(1, "aws"), None);
+        assert_eq!(cache.push(2, "gcp"), None);
+        assert_eq!(cache.push(1, "amazon"), Some((1, "aws")));
+        assert_eq!(cache.push(3, "azure"), Some((2, "gcp")));
+        assert_eq!(cache.keys().collect::<Vec<_>>(), [&3, &1]);
+    }
+
+    #[test]
+    fn test_update() {
+        let mut cache = Cache::new(2);
+
+        cache.insert(1, "aws");
+        cache.insert(2, "gcp");
+        assert_eq!(cache.update(&1, "amazon"), Some("aws"));
+        assert_eq!(cache.update(&3, "azure"), None);
+        assert!(!cache.contains_key(&3));
+
+        cache.insert(3, "azure");
+        assert_eq!(cache.peek(&1), Some(&"amazon"));
+        assert_eq!(cache.peek(&2), None);
+    }
+
+    #[test]
+    fn test_insert_lru() {
+        let mut cache = Cache::new(3);
+
+        cache.insert(1, "aws");
+        cache.insert(2, "gcp");
+        assert_eq!(cache.insert_lru(3, "azure"), None);
+        assert_eq!(cache.lru_key(), Some(&3));
+
+        assert_eq!(cache.insert(4, "vmware"), Some((3, "azure")));
+        assert_eq!(cache.insert_lru(5, "val"), Some((1, "aws")));
+        assert_eq!(cache.insert_lru(2, "google"), Some((2, "gcp")));
+        assert_eq!(cache.keys().copied().collect::<Vec<_>>(), [4, 5, 2]);
+        assert_eq!(cache.insert(6, "other"), Some((2, "google")));
+    }
+
+    #[test]
+    fn test_touch() {
+        let mut cache = Cache::new(2);
+
+        cache.insert(1, "aws");
+        assert!(cache.touch(&1));
+        assert_eq!(cache.head, cache.tail);
+
+        cache.insert(2, "gcp");
+        assert!(cache.touch(&1));
+        assert!(!cache.touch(&3));
+        assert_eq!(cache.extremes(), Some((&1, &2)));
+        assert_eq!(cache.insert(3, "azure"), Some((2, "gcp")));
+    }
+
+    #[test]
+    fn test_promote_demote() {
+        let mut cache = Cache::new(3);
+
+        cache.insert(1, "aws");
+        assert!(cache.promote(&1));
+        assert!(cache.demote(&1));
+        assert_eq!(cache.head, cache.tail);
+
+        cache.insert(2, "gcp");
+        cache.insert(3, "azure");
+        assert!(cache.promote(&1));
+        assert!(cache.promote(&1));
+        assert_eq!(cache.keys().collect::<Vec<_>>(), [&1, &3, &2]);
+
+        assert!(cache.demote(&1));
+        assert!(cache.demote(&1));
+        assert_eq!(cache.keys().collect::<Vec<_>>(), [&3, &2, &1]);
+        assert_eq!(cache.iter().rev().count(), 3);
+        assert!(!cache.promote(&4));
+        assert!(!cache.demote(&4));
+
+        cache.insert(4, "vmware");
+        assert_eq!(cache.peek(&1), None);
+    }
+
+    #[test]
+    fn test_pop_lru() {
+        let mut cache = Cache::new(3);
+
+        cache.insert(1, "aws".to_owned());
+        cache.insert(2, "gcp".to_owned());
+        cache.insert(3, "azure".to_owned());
+        cache.get(&1);
+
+        assert_eq!(cache.pop_lru(), Some((2, "gcp".to_owned())));
+        assert_eq!(cache.pop_lru(), Some((3, "azure".to_owned())));
+        assert_eq!(cache.pop_lru(), Some((1, "aws".to_owned())));
+        assert_eq!(cache.pop_lru(), None);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_peek_lru() {
+        let mut cache = Cache::new(2);
+        assert_eq!(cache.peek_lru(), None);
+
+        cache.insert(1, "aws".to_owned());
+        cache.insert(2, "gcp".to_owned());
+        assert_eq!(cache.peek_lru(), Some((&1, &"aws".to_owned())));
+        assert_eq!(cache.peek_lru(), Some((&1, &"aws".to_owned())));
+
+        cache.get(&1);
+        assert_eq!(cache.peek_lru(), Some((&2, &"gcp".to_owned())));
+    }
+
+    #[test]
+    fn test_lru_mru_key() {
+        let mut cache = Cache::new(3);
+        assert_eq!(cache.lru_key(), None);
+        assert_eq!(cache.mru_key(), None);
+
+        cache.insert(1, "aws");
+        cache.insert(2, "gcp");
+        cache.insert(3, "azure");
+        assert_eq!(cache.lru_key(), Some(&1));
+        assert_eq!(cache.mru_key(), Some(&3));
+
+        cache.get(&1);
+        assert_eq!(cache.lru_key(), Some(&2));
+        assert_eq!(cache.mru_key(), Some(&1));
+    }
+
+    #[test]
+    fn test_send() {
+        let mut cache = Cache::new(2);
+        cache.insert(1, "aws".to_owned());
+
+        let cache = std::thread::spawn(move || {
+            cache.insert(2, "gcp".to_owned());
+            assert_eq!(cache.get(&1), Some(&"aws".to_owned()));
+            cache
+        })
+        .join()
+        .unwrap();
+
+        assert_eq!(cache.peek(&2), Some(&"gcp".to_owned()));
+    }
+
+    #[test]
+    fn test_iter() {
+        let mut cache = Cache::new(3);
+
+        cache.insert(1, "aws".to_owned());
+        cache.insert(2, "gcp".to_owned());
+        cache.insert(3, "azure".to_owned());
+        cache.get(&1);
+        cache.insert(4, "vmware".to_owned());
+
+        let entries: Vec<_> = cache.iter().collect();
+        assert_eq!(
+            entries,
+            [
+                (&4, &"vmware".to_owned()),
+                (&1, &"aws".to_owned()),
+                (&3, &"azure".to_owned()),
+            ]
+        );
+        assert_eq!(cache.iter().len(), 3);
+        assert_eq!(cache.iter().next_back(), Some((&3, &"azure".to_owned())));
+        assert_eq!(
+            cache.iter_lru().map(|(key, _)| *key).collect::<Vec<_>>(),
+            [3, 1, 4]
+        );
+        assert_eq!(
+            cache.most_recent(2),
+            [(&4, &"vmware".to_owned()), (&1, &"aws".to_owned())]
+        );
+        assert_eq!(cache.most_recent(5).len(), 3);
+        assert_eq!(
+            cache.least_recent(2),
+            [(&3, &"azure".to_owned()), (&1, &"aws".to_owned())]
+        );
+        assert_eq!(cache.least_recent(5).len(), 3);
+        assert_eq!(
+            cache.to_vec(),
+            [
+                (4, "vmware".to_owned()),
+                (1, "aws".to_owned()),
+                (3, "azure".to_owned()),
+            ]
+        );
+
+        assert_eq!(cache.keys().collect::<Vec<_>>(), [&4, &1, &3]);
+        assert_eq!(
+            cache.values().collect::<Vec<_>>(),
+            [&"vmware".to_owned(), &"aws".to_owned(), &"azure".to_owned()]
+        );
+    }
+
+    #[test]
+    fn test_default() {
+        let mut cache: Cache<u32, String> = Cache::default();
+        assert_eq!(cache.capacity(), DEFAULT_CAPACITY);
+        assert!(cache.is_empty());
+
+        cache.insert(1, "aws".to_owned());
+        assert_eq!(cache.get(&1), Some(&"aws".to_owned()));
+    }
+
+    #[test]
+    fn test_from_iter() {
+        let cache: Cache<_, _> = [(1, "aws"), (2, "gcp"), (1, "amazon"), (3, "azure")]
+            .into_iter()
+            .collect();
+
+        assert_eq!(cache.capacity(), 4);
+        assert_eq!(
+            cache.iter().collect::<Vec<_>>(),
+            [(&3, &"azure"), (&1, &"amazon"), (&2, &"gcp")]
+        );
+    }
+
+    #[test]
+    fn test_extend() {
+        let mut cache = Cache::new(3);
+
+        cache.insert(1, "aws");
+        cache.insert(2, "gcp");
+        cache.get(&1);
+        cache.extend([(3, "azure"), (4, "vmware")]);
+
+        assert_eq!(cache.keys().collect::<Vec<_>>(), [&4, &3, &1]);
+    }
+
+    #[test]
+    fn test_count_matching() {
+        let mut cache = Cache::new(4);
+
+        for key in 1..=4 {
+            cache.insert(key, key * 10);
+        }
+
+        assert_eq!(cache.count_matching(|key, _| key % 2 == 0), 2);
+        assert_eq!(cache.count_matching(|_, &value| value > 40), 0);
+        assert_eq!(cache.lru_key(), Some(&1));
+    }
+
+    #[test]
+    fn test_index() {
+        let mut cache = Cache::new(2);
+
+        cache.insert("aws".to_owned(), 1);
+        cache.insert("gcp".to_owned(), 2);
+        assert_eq!(cache["aws"], 1);
+        assert_eq!(cache.lru_key().map(String::as_str), Some("aws"));
+    }
+
+    #[test]
+    #[should_panic(expected = "key not found in cache")]
+    fn test_index_missing() {
+        let cache: Cache<i32, i32> = Cache::new(2);
+        let _ = cache[&1];
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn test_invariants() {
+        let mut seed: u32 = 42;
+        let mut next = move || {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            seed >> 16
+        };
+
+        for capacity in 0..4 {
+            let mut cache = Cache::new(capacity);
+
+            for _ in 0..1000 {
+                let key = next() % 5;
+
+                match next() % 8 {
+                    0 | 1 => drop(cache.insert(key, key)),
+                    2 => drop(cache.get(&key)),
+                    3 => drop(cache.remove(&key)),
+                    4 => drop(cache.push(key, key)),
+                    5 => drop(cache.pop_lru()),
+                    6 => drop(cache.demote(&key)),
+                    _ => cache.retain(|k, _| *k != key),
+                }
+
+                cache.check_invariants();
+                assert!(cache.len() <= capacity);
+            }
+        }
+    }
+
+    #[test]
+    fn test_eviction_reuses_node() {
+        let mut cache = Cache::new(2);
+
+        cache.insert(1, "aws".to_owned());
+        cache.insert(2, "gcp".to_owned());
+        let tail = cache.tail;
+
+        assert_eq!(
+            cache.insert(3, "azure".to_owned()),
+            Some((1, "aws".to_owned()))
+        );
+        assert_eq!(cache.head, tail);
+        assert_eq!(cache.map.get(&3).copied(), tail);
+        assert_eq!(cache.peek(&3), Some(&"azure".to_owned()));
+    }
+
+    #[test]
+    fn test_insert_clones() {
+        struct Key(i32, Rc<Cell<usize>>);
+
+        impl PartialEq for Key {
+            fn eq(&self, other: &Self) -> bool {
+                self.0 == other.0
+            }
+        }
+
+        impl Eq for Key {}
+
+        impl std::hash::Hash for Key {
+            fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+                self.0.hash(state);
+            }
+        }
+
+        impl Clone for Key {
+            fn clone(&self) -> Self {
+                self.1.set(self.1.get() + 1);
+                Self(self.0, self.1.clone())
+            }
+        }
+
+        let clones = Rc::new(Cell::new(0));
+        let mut cache = Cache::new(2);
+
+        cache.insert(Key(1, clones.clone()), "aws");
+        assert_eq!(clones.get(), 1);
+
+        cache.insert(Key(1, clones.clone()), "amazon");
+        cache.insert(Key(1, clones.clone()), "amzn");
+        assert_eq!(clones.get(), 1);
+    }
+
+    #[test]
+    fn test_memory_footprint() {
+        let mut cache = Cache::new(4);
+        assert_eq!(cache.memory_footprint(), 0);
+
+        cache.insert(1, "aws".to_owned());
+        cache.insert(2, "gcp".to_owned());
+        let footprint = cache.memory_footprint();
+        assert!(footprint >= 2 * std::mem::size_of::<Node<i32, String>>());
+        assert_eq!(
+            cache.memory_footprint_with(|_, value| value.capacity()),
+            footprint + 6
+        );
+    }
+
+    #[test]
+    fn test_pin() {
+        let mut cache = Cache::new(3);
+
+        cache.insert(0, 0);
+        assert!(cache.pin(&0));
+        assert!(!cache.pin(&-1));
+
+        for key in 1..100 {
+            cache.insert(key, key);
+        }
+
+        assert_eq!(cache.peek(&0), Some(&0));
+        assert_eq!(cache.lru_key(), Some(&0));
+        assert_eq!(cache.keys().copied().collect::<Vec<_>>(), [99, 98, 0]);
+
+        assert!(cache.pin(&98));
+        assert!(cache.pin(&99));
+        assert_eq!(cache.insert(100, 100), Some((100, 100)));
+        assert_eq!(cache.len(), 3);
+
+        cache.resize(1);
+        assert_eq!(cache.len(), 3);
+        assert!(cache.clone().is_pinned(&98));
+
+        assert!(cache.unpin(&0));
+        cache.resize(2);
+        assert_eq!(cache.keys().copied().collect::<Vec<_>>(), [99, 98]);
+    }
+
+    #[test]
+    fn test_rekey() {
+        let mut cache = Cache::new(3);
+
+        cache.insert(1, "aws");
+        cache.insert(2, "gcp");
+        cache.insert(3, "azure");
+
+        assert!(cache.rekey(&2, 20));
+        assert!(!cache.rekey(&2, 30));
+        assert!(!cache.rekey(&1, 3));
+        assert!(!cache.rekey(&1, 1));
+
+        assert_eq!(cache.keys().copied().collect::<Vec<_>>(), [3, 20, 1]);
+        assert_eq!(cache.peek(&20), Some(&"gcp"));
+        assert!(!cache.contains_key(&2));
+    }
+
+    #[test]
+    fn test_transfer_to() {
+        let mut l1 = Cache::new(2);
+        let mut l2 = Cache::new(1);
+
+        l1.insert("aws".to_owned(), 1);
+        l1.insert("gcp".to_owned(), 2);
+        l2.insert("azure".to_owned(), 3);
+
+        assert_eq!(
+            l1.transfer_to("aws", &mut l2),
+            Transfer::Moved(Some(("azure".to_owned(), 3)))
+        );
+        assert_eq!(l1.transfer_to("aws", &mut l2), Transfer::Missing);
+        assert_eq!(l1.len(), 1);
+        assert_eq!(l2.keys().collect::<Vec<_>>(), ["aws"]);
+        assert_eq!(l2.stats().evictions, 1);
+
+        l1.insert("aws".to_owned(), 4);
+        assert_eq!(
+            l1.transfer_to("aws", &mut l2),
+            Transfer::Moved(Some(("aws".to_owned(), 1)))
+        );
+        assert_eq!(l2.peek("aws"), Some(&4));
+    }
+
+    #[test]
+    fn test_transfer_to_rejected() {
+        let mut l1 = Cache::new(2);
+        let mut l2 = Cache::new(0);
+
+        l1.insert("aws".to_owned(), 1);
+        assert_eq!(
+            l1.transfer_to("aws", &mut l2),
+            Transfer::Rejected("aws".to_owned(), 1)
+        );
+        assert!(l1.is_empty());
+        assert!(l2.is_empty());
+    }
+
+    #[test]
+    fn test_merge() {
+        let drops = Rc::new(Cell::new(0));
+        let mut a = Cache::new(3);
+        let mut b = Cache::new(3);
+
+        a.insert(1, DropCounter(drops.clone()));
+        a.insert(2, DropCounter(drops.clone()));
+        b.insert(2, DropCounter(drops.clone()));
+        b.insert(3, DropCounter(drops.clone()));
+        b.insert(4, DropCounter(drops.clone()));
+
+        a.merge(b);
+        assert_eq!(drops.get(), 2);
+        assert_eq!(a.keys().copied().collect::<Vec<_>>(), [4, 3, 2]);
+
+        drop(a);
+        assert_eq!(drops.get(), 5);
+    }
+
+    #[test]
+    fn test_replace_all() {
+        let drops = Rc::new(Cell::new(0));
+        let mut cache = Cache::new(2);
+
+        cache.insert(1, DropCounter(drops.clone()));
+        cache.insert(2, DropCounter(drops.clone()));
+
+        let counter = drops.clone();
+        cache.replace_all((3..6).map(|key| {
+            assert_eq!(counter.get(), 2);
+            (key, DropCounter(counter.clone()))
+        }));
+
+        assert_eq!(drops.get(), 3);
+        assert_eq!(cache.keys().copied().collect::<Vec<_>>(), [5, 4]);
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot insert into a cache full of pinned entries")]
+    fn test_get_or_insert_pinned() {
+        let mut cache = Cache::new(1);
+
+        cache.insert(1, "aws");
+        cache.pin(&1);
+        cache.get_or_insert(2, "gcp");
+    }
+
+    #[test]
+    fn test_insert_many() {
+        let mut cache = Cache::new(2);
+        cache.on_evict(|_, _| unreachable!());
+
+        cache.insert(1, "aws");
+        let evicted = cache.insert_many([(2, "gcp"), (1, "amazon"), (3, "azure"), (4, "vmware")]);
+
+        assert_eq!(evicted, [(2, "gcp"), (1, "amazon")]);
+        assert_eq!(cache.keys().collect::<Vec<_>>(), [&4, &3]);
+        assert_eq!(cache.stats().evictions, 2);
+    }
+
+    #[test]
+    fn test_eq() {
+        let mut a = Cache::new(2);
+        let mut b = Cache::new(2);
+
+        a.insert(1, "aws");
+        a.insert(2, "gcp");
+        b.insert(2, "gcp");
+        b.insert(1, "aws");
+        assert_ne!(a, b);
+
+        b.get(&2);
+        assert_eq!(a, b);
+
+        b.resize(3);
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn test_drain() {
+        let drops = Rc::new(Cell::new(0));
+        let mut cache = Cache::new(3);
+
+        cache.insert(1, DropCounter(drops.clone()));
+        cache.insert(2, DropCounter(drops.clone()));
+        cache.insert(3, DropCounter(drops.clone()));
+
+        let mut drain = cache.drain();
+        assert_eq!(drain.len(), 3);
+        assert_eq!(drain.next().map(|(key, _)| key), Some(3));
+        assert_eq!(drops.get(), 1);
+
+        drop(drain);
+        assert_eq!(drops.get(), 3);
+        assert!(cache.is_empty());
+        assert_eq!(cache.capacity(), 3);
+
+        cache.insert(4, DropCounter(drops.clone()));
+        assert_eq!(cache.drain().map(|(key, _)| key).collect::<Vec<_>>(), [4]);
+    }
+
+    #[test]
+    fn test_hash() {
+        let hash = |cache: &Cache<i32, &str>| {
+            let mut hasher = DefaultHasher::new();
+            std::hash::Hash::hash(cache, &mut hasher);
+            std::hash::Hasher::finish(&hasher)
+        };
+
+        let mut a = Cache::new(2);
+        let mut b = Cache::new(2);
+
+        a.insert(1, "aws");
+        a.insert(2, "gcp");
+        b.insert(2, "gcp");
+        b.insert(1, "aws");
+        assert_ne!(hash(&a), hash(&b));
+
+        b.get(&2);
+        assert_eq!(a, b);
+        assert_eq!(hash(&a), hash(&b));
+    }
+
+    #[test]
+    fn test_debug() {
+        let mut cache = Cache::new(3);
+
+        cache.insert(1, "aws");
+        cache.insert(2, "gcp");
+        cache.insert(3, "azure");
+        assert_eq!(
+            format!("{:?}", cache),
+            r#"Cache { capacity: 3, len: 3, entries: {3: "azure", 2: "gcp", 1: "aws"} }"#
+        );
+        assert_eq!(cache.peek_lru(), Some((&1, &"aws")));
+    }
+
+    #[test]
+    fn test_clone() {
+        let mut cache = Cache::new(3);
+
+        cache.insert(1, "aws".to_owned());
+        cache.insert(2, "gcp".to_owned());
+        cache.insert(3, "azure".to_owned());
+        cache.get(&1);
+
+        let mut clone = cache.clone();
+        assert_eq!(clone.capacity(), 3);
+        assert!(clone.iter().eq(cache.iter()));
+
+        clone.get_mut(&2).unwrap().push_str("-edited");
+        clone.insert(4, "vmware".to_owned());
+        assert_eq!(clone.peek(&3), None);
+        assert_eq!(cache.peek(&2), Some(&"gcp".to_owned()));
+        assert_eq!(cache.keys().collect::<Vec<_>>(), [&1, &3, &2]);
+
+        drop(cache);
+        assert_eq!(clone.get(&2), Some(&"gcp-edited".to_owned()));
+
+        let mut cache = Cache::new(2);
+        cache.enable_occupancy_histogram(2);
+        cache.insert(1, "aws");
+        assert_eq!(
+            cache.clone().occupancy_histogram(),
+            cache.occupancy_histogram()
+        );
+    }
+
+    #[test]
+    fn test_iter_mut() {
+        let mut cache = Cache::new(3);
+
+        cache.insert(1, 10);
+        cache.insert(2, 20);
+        cache.insert(3, 30);
+
+        for (key, value) in cache.iter_mut() {
+            *value += key;
+        }
+
+        let (_, value) = cache.iter_mut().next_back().unwrap();
+        *value = 0;
+
+        assert_eq!(cache.to_vec(), [(3, 33), (2, 22), (1, 0)]);
+        assert_eq!((&mut cache).into_iter().len(), 3);
+    }
+
+    #[test]
+    fn test_for_each() {
+        let mut cache = Cache::new(3);
+
+        cache.insert(1, 10);
+        cache.insert(2, 20);
+        cache.insert(3, 30);
+
+        cache.for_each(|key, value| {
+            *value += key;
+            ControlFlow::Continue(())
+        });
+        assert_eq!(cache.to_vec(), [(3, 33), (2, 22), (1, 11)]);
+
+        let mut visited = Vec::new();
+        cache.for_each(|&key, value| {
+            visited.push(key);
+            *value = 0;
+            if key == 2 {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        });
+        assert_eq!(visited, [3, 2]);
+        assert_eq!(cache.to_vec(), [(3, 0), (2, 0), (1, 11)]);
+    }
+
+    #[test]
+    fn test_into_iter() {
+        let drops = Rc::new(Cell::new(0));
+        let mut cache = Cache::new(3);
+
+        cache.insert(1, DropCounter(drops.clone()));
+        cache.insert(2, DropCounter(drops.clone()));
+        cache.insert(3, DropCounter(drops.clone()));
+
+        let mut iter = cache.into_iter();
+        assert_eq!(iter.len(), 3);
+        assert_eq!(iter.next().map(|(key, _)| key), Some(3));
+        assert_eq!(iter.next_back().map(|(key, _)| key), Some(1));
+        assert_eq!(drops.get(), 2);
+
+        drop(iter);
+        assert_eq!(drops.get(), 3);
+    }
+
+    #[test]
+    fn test_into_lru_vec() {
+        let drops = Rc::new(Cell::new(0));
+        let mut cache = Cache::new(3);
+
+        cache.insert(1, DropCounter(drops.clone()));
+        cache.insert(2, DropCounter(drops.clone()));
+        cache.insert(3, DropCounter(drops.clone()));
+        cache.get(&1);
+
+        let entries = cache.into_lru_vec();
+        assert_eq!(drops.get(), 0);
+        assert_eq!(
+            entries.iter().map(|(key, _)| *key).collect::<Vec<_>>(),
+            [2, 3, 1]
+        );
+
+        drop(entries);
+        assert_eq!(drops.get(), 3);
+    }
+
+    #[test]
+    fn test_apply_op() {
+        // A xorshift generator, so that failures are reproducible.
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let mut next = move |bound: u64| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state % bound
+        };
+
+        let mut cache = Cache::new(4);
+        // The entries from the most recently used to the least recently used.
+        let mut model = std::collections::VecDeque::new();
+        let mut capacity = 4;
+
+        for value in 0..10_000 {
+            let key = next(8);
+            let op = match next(20) {
+                0 => Op::Clear,
+                1 => Op::Resize(next(6) as usize),
+                2..=9 => Op::Insert(key, value),
+                10..=15 => Op::Get(key),
+                _ => Op::Remove(key),
+            };
+
+            let position = model.iter().position(|&(k, _)| k == key);
+
+            match op {
+                Op::Insert(key, value) => {
+                    if let Some(position) = position {
+                        model.remove(position);
+                    } else if model.len() == capacity {
+                        model.pop_back();
+                    }
+
+                    if capacity > 0 {
+                        model.push_front((key, value));
+                    }
+                }
+                Op::Get(_) => {
+                    if let Some(entry) = position.and_then(|position| model.remove(position)) {
+                        model.push_front(entry);
+                    }
+                }
+                Op::Remove(_) => {
+                    position.map(|position| model.remove(position));
+                }
+                Op::Clear => model.clear(),
+                Op::Resize(n) => {
+                    capacity = n;
+                    model.truncate(n);
+                }
+            }
+
+            cache.apply_op(op.clone());
+            assert_eq!(cache.verify(), Ok(()), "after {op:?}");
+            assert!(
+                cache.to_vec().into_iter().eq(model.iter().copied()),
+                "after {op:?}"
+            );
+        }
     }
 }
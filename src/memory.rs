@@ -0,0 +1,175 @@
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::mem;
+
+use crate::WeightedCache;
+
+/// An LRU cache bounded by the number of bytes its entries take up.
+///
+/// The size of an entry is the inline size of its key and value, as reported
+/// by [`mem::size_of_val`], plus whatever the sizer the cache was created with
+/// reports for the memory they own on the heap, such as the capacity of a
+/// `String`. It does not account for the bookkeeping of the cache itself.
+///
+/// This is a [`WeightedCache`] with that size as the weight, so an entry that
+/// alone exceeds the budget is rejected by [`MemoryBoundedCache::insert`] and
+/// handed straight back.
+pub struct MemoryBoundedCache<K, V, S = RandomState> {
+    cache: WeightedCache<K, V, S>,
+}
+
+impl<K, V> MemoryBoundedCache<K, V>
+where
+    K: std::cmp::Eq + std::hash::Hash + Clone,
+{
+    pub fn new<F>(max_bytes: usize, size_of: F) -> Self
+    where
+        F: Fn(&K, &V) -> usize + Send + Sync + 'static,
+    {
+        Self::with_hasher(max_bytes, size_of, RandomState::new())
+    }
+}
+
+impl<K, V, S> MemoryBoundedCache<K, V, S>
+where
+    K: std::cmp::Eq + std::hash::Hash + Clone,
+    S: std::hash::BuildHasher,
+{
+    pub fn with_hasher<F>(max_bytes: usize, size_of: F, hasher: S) -> Self
+    where
+        F: Fn(&K, &V) -> usize + Send + Sync + 'static,
+    {
+        let weigher = move |key: &K, value: &V| {
+            mem::size_of_val(key) + mem::size_of_val(value) + size_of(key, value)
+        };
+
+        Self {
+            cache: WeightedCache::with_weigher_and_hasher(max_bytes, weigher, hasher),
+        }
+    }
+
+    /// Caps the number of entries a single insertion evicts, as with
+    /// [`WeightedCache::with_max_evictions_per_op`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is 0.
+    pub fn with_max_evictions_per_op(self, n: usize) -> Self {
+        Self {
+            cache: self.cache.with_max_evictions_per_op(n),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cache.is_empty()
+    }
+
+    pub fn max_bytes(&self) -> usize {
+        self.cache.max_weight()
+    }
+
+    pub fn total_bytes(&self) -> usize {
+        self.cache.total_weight()
+    }
+
+    /// Returns how many more bytes fit before an insertion evicts, which is 0
+    /// while the cache is over its budget.
+    pub fn remaining_bytes(&self) -> usize {
+        self.cache.remaining_weight()
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        self.cache.contains_key(key)
+    }
+
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        self.cache.get(key)
+    }
+
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        self.cache.peek(key)
+    }
+
+    /// Inserts a key-value pair and evicts the least recently used entries
+    /// until the cache is back within its budget. See
+    /// [`WeightedCache::insert`] for what is returned.
+    pub fn insert(&mut self, key: K, value: V) -> Vec<(K, V)> {
+        self.cache.insert(key, value)
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        self.cache.remove(key)
+    }
+
+    pub fn pop_lru(&mut self) -> Option<(K, V)> {
+        self.cache.pop_lru()
+    }
+
+    pub fn clear(&mut self) {
+        self.cache.clear();
+    }
+
+    /// Returns an iterator over the entries, from the most recently used to
+    /// the least recently used.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.cache.iter()
+    }
+}
+
+impl<K, V, S> fmt::Debug for MemoryBoundedCache<K, V, S>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MemoryBoundedCache")
+            .field("cache", &self.cache)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_bounded_cache() {
+        let entry = mem::size_of::<u32>() + mem::size_of::<String>();
+        let mut cache =
+            MemoryBoundedCache::new(2 * entry + 16, |_, value: &String| value.capacity());
+
+        assert!(cache.insert(1, String::with_capacity(8)).is_empty());
+        assert!(cache.insert(2, String::with_capacity(8)).is_empty());
+        assert_eq!(cache.total_bytes(), 2 * entry + 16);
+
+        let evicted = cache.insert(3, String::with_capacity(4));
+        assert_eq!(evicted.len(), 1);
+        assert_eq!(evicted[0].0, 1);
+        assert_eq!(cache.total_bytes(), 2 * entry + 12);
+        assert_eq!(cache.remaining_bytes(), 4);
+
+        let rejected = cache.insert(4, String::with_capacity(64));
+        assert_eq!(rejected[0].0, 4);
+        assert_eq!(cache.len(), 2);
+    }
+}
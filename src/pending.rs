@@ -0,0 +1,196 @@
+use std::collections::hash_map::RandomState;
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::Cache;
+
+/// An LRU cache that remembers which keys are being computed, so that a
+/// value is only computed once even if it is asked for again in the
+/// meantime.
+///
+/// [`PendingCache::get_or_begin`] either returns the cached value, reports
+/// that the key is pending, or marks it as pending and hands out a
+/// [`Placeholder`]. The placeholder must then be passed back to
+/// [`PendingCache::complete`] with the value, or to
+/// [`PendingCache::abandon`] if the computation failed; a dropped placeholder
+/// leaves its key pending for good. Pending keys don't count towards the
+/// length or capacity of the cache.
+///
+/// This only coalesces requests within a single thread, such as the tasks of
+/// a single-threaded executor. Sharing it between threads takes a lock, which
+/// must not be held while the value is computed.
+pub struct PendingCache<K, V, S = RandomState> {
+    cache: Cache<K, V, S>,
+    pending: HashSet<K, S>,
+}
+
+/// What [`PendingCache::get_or_begin`] found for a key.
+#[derive(Debug)]
+pub enum PendingLookup<'a, K, V> {
+    /// The value is cached.
+    Ready(&'a V),
+    /// Another caller is computing the value.
+    Pending,
+    /// Nobody was computing the value, so the key is now pending and the
+    /// caller is to compute it.
+    Begun(Placeholder<K>),
+}
+
+/// The token of a pending key, to be passed back to
+/// [`PendingCache::complete`] or [`PendingCache::abandon`].
+#[must_use = "the key stays pending until the placeholder is completed or abandoned"]
+#[derive(Debug, PartialEq, Eq)]
+pub struct Placeholder<K> {
+    key: K,
+}
+
+impl<K> Placeholder<K> {
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+}
+
+impl<K, V> PendingCache<K, V>
+where
+    K: std::cmp::Eq + std::hash::Hash + Clone,
+{
+    pub fn new(capacity: usize) -> Self {
+        Self::with_hasher(capacity, RandomState::new())
+    }
+}
+
+impl<K, V, S> PendingCache<K, V, S>
+where
+    K: std::cmp::Eq + std::hash::Hash + Clone,
+    S: std::hash::BuildHasher,
+{
+    pub fn with_hasher(capacity: usize, hasher: S) -> Self
+    where
+        S: Clone,
+    {
+        Self {
+            cache: Cache::with_hasher(capacity, hasher.clone()),
+            pending: HashSet::with_hasher(hasher),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cache.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.cache.capacity()
+    }
+
+    pub fn is_pending<Q>(&self, key: &Q) -> bool
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        self.pending.contains(key)
+    }
+
+    /// Returns the cached value for the key, making it the most recently used
+    /// entry, or else marks the key as pending unless it already is.
+    pub fn get_or_begin(&mut self, key: K) -> PendingLookup<'_, K, V> {
+        // Checking first, rather than matching on `get`, lets the borrow of the
+        // value end on a miss.
+        if self.cache.contains_key(&key) {
+            return self
+                .cache
+                .get(&key)
+                .map_or(PendingLookup::Pending, PendingLookup::Ready);
+        }
+
+        if !self.pending.insert(key.clone()) {
+            return PendingLookup::Pending;
+        }
+
+        PendingLookup::Begun(Placeholder { key })
+    }
+
+    /// Caches the value of a pending key, with the same return value as
+    /// [`Cache::insert`].
+    pub fn complete(&mut self, placeholder: Placeholder<K>, value: V) -> Option<(K, V)> {
+        self.pending.remove(&placeholder.key);
+        self.cache.insert(placeholder.key, value)
+    }
+
+    /// Gives up on a pending key, so that the next
+    /// [`PendingCache::get_or_begin`] for it begins anew.
+    pub fn abandon(&mut self, placeholder: Placeholder<K>) {
+        self.pending.remove(&placeholder.key);
+    }
+
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        self.cache.peek(key)
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        self.cache.remove(key)
+    }
+}
+
+impl<K, V, S> fmt::Debug for PendingCache<K, V, S>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PendingCache")
+            .field("cache", &self.cache)
+            .field("pending", &self.pending)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pending_cache() {
+        let mut cache = PendingCache::new(2);
+
+        let PendingLookup::Begun(placeholder) = cache.get_or_begin(1) else {
+            panic!("expected the key to be begun");
+        };
+
+        assert_eq!(placeholder.key(), &1);
+        assert!(cache.is_pending(&1));
+        assert!(matches!(cache.get_or_begin(1), PendingLookup::Pending));
+        assert!(cache.is_empty());
+
+        assert_eq!(cache.complete(placeholder, "aws"), None);
+        assert!(!cache.is_pending(&1));
+        assert!(matches!(
+            cache.get_or_begin(1),
+            PendingLookup::Ready(&"aws")
+        ));
+    }
+
+    #[test]
+    fn test_abandon() {
+        let mut cache = PendingCache::<_, &str>::new(2);
+
+        let PendingLookup::Begun(placeholder) = cache.get_or_begin(1) else {
+            panic!("expected the key to be begun");
+        };
+
+        cache.abandon(placeholder);
+        assert!(!cache.is_pending(&1));
+        assert!(matches!(cache.get_or_begin(1), PendingLookup::Begun(_)));
+    }
+}
@@ -0,0 +1,161 @@
+use std::cell::{Ref, RefCell};
+use std::collections::hash_map::RandomState;
+use std::fmt;
+
+use crate::Cache;
+
+/// An LRU cache whose methods all take `&self`, so that it can be shared
+/// through `&` or `Rc` within a single thread.
+///
+/// The cache lives in a [`RefCell`], which makes this type `!Sync`: it cannot
+/// be shared between threads, for which a [`crate::ShardedCache`] or a lock
+/// around a [`Cache`] is needed instead. The values are handed out as [`Ref`]
+/// guards, and every method that changes the cache, including
+/// [`RefCellCache::get`], panics while one of them is alive.
+pub struct RefCellCache<K, V, S = RandomState> {
+    cache: RefCell<Cache<K, V, S>>,
+}
+
+impl<K, V> RefCellCache<K, V>
+where
+    K: std::cmp::Eq + std::hash::Hash + Clone,
+{
+    pub fn new(capacity: usize) -> Self {
+        Self::with_hasher(capacity, RandomState::new())
+    }
+}
+
+impl<K, V, S> RefCellCache<K, V, S>
+where
+    K: std::cmp::Eq + std::hash::Hash + Clone,
+    S: std::hash::BuildHasher,
+{
+    pub fn with_hasher(capacity: usize, hasher: S) -> Self {
+        Self::from(Cache::with_hasher(capacity, hasher))
+    }
+
+    pub fn len(&self) -> usize {
+        self.cache.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cache.borrow().is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.cache.borrow().capacity()
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        self.cache.borrow().contains_key(key)
+    }
+
+    /// Returns the value for the key, making it the most recently used entry.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a value returned by this cache is still borrowed.
+    pub fn get<Q>(&self, key: &Q) -> Option<Ref<'_, V>>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        self.cache.borrow_mut().get(key)?;
+        self.peek(key)
+    }
+
+    /// Returns the value for the key without changing the recency order.
+    pub fn peek<Q>(&self, key: &Q) -> Option<Ref<'_, V>>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        Ref::filter_map(self.cache.borrow(), |cache| cache.peek(key)).ok()
+    }
+
+    /// Inserts a key-value pair, with the same return value as
+    /// [`Cache::insert`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if a value returned by this cache is still borrowed.
+    pub fn insert(&self, key: K, value: V) -> Option<(K, V)> {
+        self.cache.borrow_mut().insert(key, value)
+    }
+
+    /// # Panics
+    ///
+    /// Panics if a value returned by this cache is still borrowed.
+    pub fn remove<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        self.cache.borrow_mut().remove(key)
+    }
+
+    /// # Panics
+    ///
+    /// Panics if a value returned by this cache is still borrowed.
+    pub fn clear(&self) {
+        self.cache.borrow_mut().clear();
+    }
+
+    pub fn into_inner(self) -> Cache<K, V, S> {
+        self.cache.into_inner()
+    }
+}
+
+impl<K, V, S> From<Cache<K, V, S>> for RefCellCache<K, V, S> {
+    fn from(cache: Cache<K, V, S>) -> Self {
+        Self {
+            cache: RefCell::new(cache),
+        }
+    }
+}
+
+impl<K, V, S> fmt::Debug for RefCellCache<K, V, S>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RefCellCache")
+            .field("cache", &self.cache)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_refcell_cache() {
+        let cache = RefCellCache::new(2);
+        let shared = &cache;
+
+        shared.insert(1, "aws");
+        shared.insert(2, "gcp");
+        assert_eq!(shared.get(&1).as_deref(), Some(&"aws"));
+        assert!(shared.get(&3).is_none());
+
+        assert_eq!(cache.insert(3, "azure"), Some((2, "gcp")));
+        assert_eq!(cache.remove(&1), Some("aws"));
+        assert_eq!(cache.into_inner().keys().collect::<Vec<_>>(), [&3]);
+    }
+
+    #[test]
+    #[should_panic(expected = "already borrowed")]
+    fn test_refcell_cache_borrowed() {
+        let cache = RefCellCache::new(2);
+
+        cache.insert(1, "aws");
+        let _value = cache.get(&1);
+        cache.insert(2, "gcp");
+    }
+}
@@ -0,0 +1,198 @@
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::BuildHasher;
+use std::sync::{Mutex, MutexGuard};
+
+use crate::Cache;
+
+/// A thread-safe cache that splits its keys across several [`Cache`] shards,
+/// each behind its own lock, so that threads using different shards don't
+/// contend with each other.
+///
+/// Every shard evicts its own least recently used entry, so the recency order
+/// is only tracked per shard and the cache as a whole is an approximate LRU.
+///
+/// The methods panic if they find a shard's lock poisoned by a thread that
+/// panicked while holding it.
+pub struct ShardedCache<K, V> {
+    shards: Vec<Mutex<Cache<K, V>>>,
+    hasher: RandomState,
+}
+
+impl<K, V> ShardedCache<K, V>
+where
+    K: std::cmp::Eq + std::hash::Hash + Clone,
+{
+    /// Creates a cache of `total_capacity` entries, divided as evenly as
+    /// possible between `shards` shards. So that no shard is left without
+    /// room, which would drop every key hashed to it, there are never more
+    /// shards than entries: the shard count is clamped to `total_capacity`,
+    /// or to one shard if that is 0.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `shards` is 0.
+    pub fn new(total_capacity: usize, shards: usize) -> Self {
+        assert!(shards > 0, "a sharded cache needs at least one shard");
+
+        let shards = shards.min(total_capacity.max(1));
+        let shards = (0..shards)
+            .map(|i| {
+                let extra = usize::from(i < total_capacity % shards);
+                Mutex::new(Cache::new(total_capacity / shards + extra))
+            })
+            .collect();
+
+        Self {
+            shards,
+            hasher: RandomState::new(),
+        }
+    }
+
+    fn shard<Q>(&self, key: &Q) -> MutexGuard<'_, Cache<K, V>>
+    where
+        Q: ?Sized + std::hash::Hash,
+    {
+        let index = self.hasher.hash_one(key) % self.shards.len() as u64;
+        self.shards[index as usize].lock().unwrap()
+    }
+
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| shard.lock().unwrap().len())
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|shard| shard.lock().unwrap().capacity())
+            .sum()
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        self.shard(key).contains_key(key)
+    }
+
+    /// Returns a clone of the value for the key, making it the most recently
+    /// used entry of its shard.
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+        V: Clone,
+    {
+        self.shard(key).get(key).cloned()
+    }
+
+    /// Inserts a key-value pair into its shard. See [`Cache::insert`] for
+    /// what is returned.
+    pub fn insert(&self, key: K, value: V) -> Option<(K, V)> {
+        self.shard(&key).insert(key, value)
+    }
+
+    pub fn remove<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        self.shard(key).remove(key)
+    }
+
+    pub fn clear(&self) {
+        for shard in &self.shards {
+            shard.lock().unwrap().clear();
+        }
+    }
+}
+
+impl<K, V> fmt::Debug for ShardedCache<K, V>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ShardedCache")
+            .field("shards", &self.shards)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn test_sharded_cache() {
+        let cache = ShardedCache::new(10, 4);
+        assert_eq!(cache.shard_count(), 4);
+        assert_eq!(cache.capacity(), 10);
+
+        cache.insert("aws".to_owned(), 1);
+        cache.insert("gcp".to_owned(), 2);
+        assert_eq!(cache.get("aws"), Some(1));
+        assert_eq!(cache.remove("gcp"), Some(2));
+        assert!(!cache.contains_key("gcp"));
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_more_shards_than_capacity() {
+        let cache = ShardedCache::new(3, 8);
+        assert_eq!(cache.shard_count(), 3);
+        assert_eq!(cache.capacity(), 3);
+
+        for key in 0..100 {
+            cache.insert(key, key);
+            assert!(cache.contains_key(&key));
+        }
+
+        let cache = ShardedCache::<u32, u32>::new(0, 4);
+        assert_eq!(cache.shard_count(), 1);
+        assert_eq!(cache.capacity(), 0);
+    }
+
+    #[test]
+    fn test_sharded_cache_threads() {
+        let cache = Arc::new(ShardedCache::new(64, 8));
+
+        let handles: Vec<_> = (0..8)
+            .map(|thread| {
+                let cache = cache.clone();
+
+                thread::spawn(move || {
+                    for i in 0..1000 {
+                        let key = (thread * 31 + i) % 200;
+                        cache.insert(key, i);
+                        cache.get(&key);
+
+                        if i % 7 == 0 {
+                            cache.remove(&key);
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert!(cache.len() <= 64);
+    }
+}
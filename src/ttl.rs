@@ -0,0 +1,519 @@
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::{Cache, EvictionCause, ListenerId};
+
+/// A source of the current time, so that expiration can be tested without
+/// sleeping.
+pub trait Clock {
+    fn now(&self) -> Instant;
+}
+
+/// The [`Clock`] backed by [`Instant::now`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+struct Timed<V> {
+    value: V,
+    inserted_at: Instant,
+    accessed_at: Instant,
+    expires_at: Option<Instant>,
+}
+
+impl<V> Timed<V> {
+    fn is_expired(&self, now: Instant) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+impl<V: fmt::Debug> fmt::Debug for Timed<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.value.fmt(f)
+    }
+}
+
+/// An LRU cache whose entries can expire after a time-to-live.
+///
+/// Expired entries are treated as absent. They are removed lazily when
+/// [`TtlCache::get`] comes across them, or eagerly by
+/// [`TtlCache::purge_expired`], and until then they still count towards the
+/// length and capacity of the cache. A lazy eviction budget, set with
+/// [`TtlCache::set_lazy_evict_budget`], spreads the cleanup over `get` and
+/// `insert` instead.
+pub struct TtlCache<K, V, C = SystemClock, S = RandomState> {
+    cache: Cache<K, Timed<V>, S>,
+    clock: C,
+    lazy_evict_budget: usize,
+}
+
+impl<K, V> TtlCache<K, V>
+where
+    K: std::cmp::Eq + std::hash::Hash + Clone,
+{
+    pub fn new(capacity: usize) -> Self {
+        Self::with_clock(capacity, SystemClock)
+    }
+}
+
+impl<K, V, C> TtlCache<K, V, C>
+where
+    K: std::cmp::Eq + std::hash::Hash + Clone,
+    C: Clock,
+{
+    pub fn with_clock(capacity: usize, clock: C) -> Self {
+        Self::with_clock_and_hasher(capacity, clock, RandomState::new())
+    }
+}
+
+impl<K, V, C, S> TtlCache<K, V, C, S>
+where
+    K: std::cmp::Eq + std::hash::Hash + Clone,
+    C: Clock,
+    S: std::hash::BuildHasher,
+{
+    pub fn with_clock_and_hasher(capacity: usize, clock: C, hasher: S) -> Self {
+        Self {
+            cache: Cache::with_hasher(capacity, hasher),
+            clock,
+            lazy_evict_budget: 0,
+        }
+    }
+
+    pub fn lazy_evict_budget(&self) -> usize {
+        self.lazy_evict_budget
+    }
+
+    /// Makes every `get` and `insert` check up to `budget` of the least
+    /// recently used entries and remove the expired ones. With a budget of 0,
+    /// the default, expired entries are only cleaned up by
+    /// [`TtlCache::purge_expired`] or when `get` comes across them.
+    pub fn set_lazy_evict_budget(&mut self, budget: usize) {
+        self.lazy_evict_budget = budget;
+    }
+
+    fn evict_lazily(&mut self) {
+        let now = self.clock.now();
+        self.cache.retain_lru_with_cause(
+            self.lazy_evict_budget,
+            |_, timed| !timed.is_expired(now),
+            EvictionCause::Expired,
+        );
+    }
+
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cache.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.cache.capacity()
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        self.peek(key).is_some()
+    }
+
+    /// Returns the value for the key and makes it the most recently used
+    /// entry. An expired entry is removed and reported as missing.
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        self.evict_lazily();
+        let now = self.clock.now();
+
+        if self.cache.peek(key)?.is_expired(now) {
+            self.cache.remove_with_cause(key, EvictionCause::Expired);
+            return None;
+        }
+
+        let timed = self.cache.get_mut(key)?;
+        timed.accessed_at = now;
+        Some(&timed.value)
+    }
+
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        let timed = self.cache.peek(key)?;
+
+        if timed.is_expired(self.clock.now()) {
+            None
+        } else {
+            Some(&timed.value)
+        }
+    }
+
+    /// Returns how long ago the key was inserted, without changing its
+    /// recency. Overwriting a key restarts its age.
+    pub fn age<Q>(&self, key: &Q) -> Option<Duration>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        let timed = self.cache.peek(key)?;
+        let now = self.clock.now();
+
+        if timed.is_expired(now) {
+            None
+        } else {
+            Some(now.saturating_duration_since(timed.inserted_at))
+        }
+    }
+
+    /// Returns when the key was last read by [`TtlCache::get`], or inserted if
+    /// it hasn't been read since.
+    pub fn last_access<Q>(&self, key: &Q) -> Option<Instant>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        let timed = self.cache.peek(key)?;
+
+        if timed.is_expired(self.clock.now()) {
+            None
+        } else {
+            Some(timed.accessed_at)
+        }
+    }
+
+    /// Returns an iterator over the entries last accessed more than `age` ago,
+    /// from the least recently used to the most recently used. Expired
+    /// entries are skipped.
+    pub fn iter_older_than(&self, age: Duration) -> impl Iterator<Item = (&K, &V)> {
+        let now = self.clock.now();
+
+        self.cache
+            .iter_lru()
+            .filter(move |(_, timed)| {
+                !timed.is_expired(now) && now.saturating_duration_since(timed.accessed_at) > age
+            })
+            .map(|(key, timed)| (key, &timed.value))
+    }
+
+    /// Inserts an entry that never expires. See [`Cache::insert`] for what is
+    /// returned, except that an expired entry, whether replaced or evicted,
+    /// is never returned and is reported to the listeners as
+    /// [`EvictionCause::Expired`].
+    pub fn insert(&mut self, key: K, value: V) -> Option<(K, V)> {
+        self.insert_timed(key, value, None)
+    }
+
+    /// Inserts an entry that expires once `ttl` has elapsed. See
+    /// [`TtlCache::insert`] for what is returned.
+    pub fn insert_with_ttl(&mut self, key: K, value: V, ttl: Duration) -> Option<(K, V)> {
+        let expires_at = self.clock.now() + ttl;
+        self.insert_timed(key, value, Some(expires_at))
+    }
+
+    fn insert_timed(&mut self, key: K, value: V, expires_at: Option<Instant>) -> Option<(K, V)> {
+        self.evict_lazily();
+        let now = self.clock.now();
+
+        // An expired entry is handed to the listeners as such, never returned
+        // as replaced or evicted.
+        if self
+            .cache
+            .peek(&key)
+            .is_some_and(|timed| timed.is_expired(now))
+        {
+            self.cache.remove_with_cause(&key, EvictionCause::Expired);
+        } else if !self.cache.contains_key(&key) {
+            self.cache.remove_insertion_victim_if(
+                |_, timed| timed.is_expired(now),
+                EvictionCause::Expired,
+            );
+        }
+
+        let timed = Timed {
+            value,
+            inserted_at: now,
+            accessed_at: now,
+            expires_at,
+        };
+        self.cache
+            .insert(key, timed)
+            .map(|(key, timed)| (key, timed.value))
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        self.cache.remove(key).map(|timed| timed.value)
+    }
+
+    /// Removes every expired entry.
+    pub fn purge_expired(&mut self) {
+        let now = self.clock.now();
+        self.cache
+            .retain_with_cause(|_, timed| !timed.is_expired(now), EvictionCause::Expired);
+    }
+
+    pub fn clear(&mut self) {
+        self.cache.clear();
+    }
+
+    /// Registers a listener as with [`Cache::register_eviction_listener`].
+    /// Entries removed because they expired are reported with
+    /// [`EvictionCause::Expired`].
+    pub fn register_eviction_listener<F>(&mut self, mut f: F) -> ListenerId
+    where
+        F: FnMut(K, V, EvictionCause) + Send + 'static,
+        V: Clone,
+    {
+        self.cache
+            .add_listener(Box::new(move |key: &K, timed: &Timed<V>, cause| {
+                f(key.clone(), timed.value.clone(), cause)
+            }))
+    }
+
+    /// Removes a listener, returning whether it was registered.
+    pub fn remove_eviction_listener(&mut self, id: ListenerId) -> bool {
+        self.cache.remove_eviction_listener(id)
+    }
+}
+
+impl<K, V, C, S> fmt::Debug for TtlCache<K, V, C, S>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TtlCache")
+            .field("cache", &self.cache)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone)]
+    struct MockClock {
+        start: Instant,
+        elapsed: Rc<Cell<Duration>>,
+    }
+
+    impl MockClock {
+        fn new() -> Self {
+            Self {
+                start: Instant::now(),
+                elapsed: Rc::new(Cell::new(Duration::ZERO)),
+            }
+        }
+
+        fn advance(&self, duration: Duration) {
+            self.elapsed.set(self.elapsed.get() + duration);
+        }
+    }
+
+    impl Clock for MockClock {
+        fn now(&self) -> Instant {
+            self.start + self.elapsed.get()
+        }
+    }
+
+    #[test]
+    fn test_ttl_cache() {
+        let clock = MockClock::new();
+        let mut cache = TtlCache::with_clock(3, clock.clone());
+
+        cache.insert_with_ttl(1, "aws".to_owned(), Duration::from_secs(10));
+        cache.insert_with_ttl(2, "gcp".to_owned(), Duration::from_secs(20));
+        cache.insert(3, "azure".to_owned());
+
+        clock.advance(Duration::from_secs(10));
+        assert_eq!(cache.peek(&1), None);
+        assert!(!cache.contains_key(&1));
+        assert_eq!(cache.len(), 3);
+        assert_eq!(cache.get(&1), None);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&2), Some(&"gcp".to_owned()));
+
+        clock.advance(Duration::from_secs(3600));
+        assert_eq!(cache.peek(&2), None);
+        assert_eq!(cache.peek(&3), Some(&"azure".to_owned()));
+    }
+
+    #[test]
+    fn test_purge_expired() {
+        let clock = MockClock::new();
+        let mut cache = TtlCache::with_clock(3, clock.clone());
+
+        cache.insert_with_ttl(1, "aws".to_owned(), Duration::from_secs(10));
+        cache.insert(2, "gcp".to_owned());
+        cache.insert_with_ttl(3, "azure".to_owned(), Duration::from_secs(5));
+
+        clock.advance(Duration::from_secs(10));
+        cache.purge_expired();
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.get(&2), Some(&"gcp".to_owned()));
+    }
+
+    #[test]
+    fn test_insert_over_expired() {
+        let clock = MockClock::new();
+        let mut cache = TtlCache::with_clock(2, clock.clone());
+        let causes = Arc::new(Mutex::new(Vec::new()));
+
+        cache.register_eviction_listener({
+            let causes = causes.clone();
+            move |key, _, cause| causes.lock().unwrap().push((key, cause))
+        });
+
+        cache.insert_with_ttl(1, "aws".to_owned(), Duration::from_secs(5));
+        cache.insert_with_ttl(2, "gcp".to_owned(), Duration::from_secs(5));
+        clock.advance(Duration::from_secs(5));
+
+        assert_eq!(cache.insert(3, "azure".to_owned()), None);
+        assert_eq!(cache.insert(2, "google".to_owned()), None);
+        assert_eq!(
+            cache.insert(4, "vmware".to_owned()),
+            Some((3, "azure".to_owned()))
+        );
+        assert_eq!(
+            *causes.lock().unwrap(),
+            [
+                (1, EvictionCause::Expired),
+                (2, EvictionCause::Expired),
+                (3, EvictionCause::Capacity),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_expired_eviction_cause() {
+        let clock = MockClock::new();
+        let mut cache = TtlCache::with_clock(3, clock.clone());
+        let causes = Arc::new(Mutex::new(Vec::new()));
+
+        cache.register_eviction_listener({
+            let causes = causes.clone();
+            move |key, _, cause| causes.lock().unwrap().push((key, cause))
+        });
+
+        cache.insert_with_ttl(1, "aws".to_owned(), Duration::from_secs(5));
+        cache.insert_with_ttl(2, "gcp".to_owned(), Duration::from_secs(5));
+        cache.insert(3, "azure".to_owned());
+        cache.remove(&3);
+
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(cache.get(&1), None);
+        cache.purge_expired();
+
+        assert_eq!(
+            *causes.lock().unwrap(),
+            [
+                (3, EvictionCause::Explicit),
+                (1, EvictionCause::Expired),
+                (2, EvictionCause::Expired),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lazy_evict_budget() {
+        let clock = MockClock::new();
+        let mut cache = TtlCache::with_clock(4, clock.clone());
+        cache.set_lazy_evict_budget(2);
+
+        cache.insert_with_ttl(1, "aws".to_owned(), Duration::from_secs(5));
+        cache.insert_with_ttl(2, "gcp".to_owned(), Duration::from_secs(5));
+        cache.insert_with_ttl(3, "azure".to_owned(), Duration::from_secs(5));
+        cache.insert(4, "vmware".to_owned());
+
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(cache.get(&4), Some(&"vmware".to_owned()));
+        assert_eq!(cache.len(), 2);
+
+        cache.insert(5, "val".to_owned());
+        assert_eq!(cache.len(), 2);
+        assert!(cache.contains_key(&4));
+    }
+
+    #[test]
+    fn test_age() {
+        let clock = MockClock::new();
+        let mut cache = TtlCache::with_clock(2, clock.clone());
+
+        cache.insert(1, "aws".to_owned());
+        clock.advance(Duration::from_secs(3));
+        cache.insert_with_ttl(2, "gcp".to_owned(), Duration::from_secs(5));
+        clock.advance(Duration::from_secs(2));
+
+        assert_eq!(cache.age(&1), Some(Duration::from_secs(5)));
+        assert_eq!(cache.age(&2), Some(Duration::from_secs(2)));
+        assert_eq!(cache.age(&3), None);
+
+        cache.insert(1, "amazon".to_owned());
+        assert_eq!(cache.age(&1), Some(Duration::ZERO));
+
+        clock.advance(Duration::from_secs(3));
+        assert_eq!(cache.age(&2), None);
+    }
+
+    #[test]
+    fn test_last_access() {
+        let clock = MockClock::new();
+        let mut cache = TtlCache::with_clock(2, clock.clone());
+
+        cache.insert(1, "aws".to_owned());
+        let inserted = clock.now();
+        clock.advance(Duration::from_secs(3));
+        assert_eq!(cache.last_access(&1), Some(inserted));
+
+        cache.get(&1);
+        assert_eq!(cache.last_access(&1), Some(clock.now()));
+        assert_eq!(cache.age(&1), Some(Duration::from_secs(3)));
+        assert_eq!(cache.last_access(&2), None);
+    }
+
+    #[test]
+    fn test_iter_older_than() {
+        let clock = MockClock::new();
+        let mut cache = TtlCache::with_clock(4, clock.clone());
+
+        cache.insert(1, "aws");
+        cache.insert(2, "gcp");
+        cache.insert_with_ttl(3, "azure", Duration::from_secs(5));
+        clock.advance(Duration::from_secs(10));
+        cache.insert(4, "vmware");
+        cache.get(&1);
+        clock.advance(Duration::from_secs(1));
+
+        let keys: Vec<_> = cache
+            .iter_older_than(Duration::from_secs(5))
+            .map(|(key, _)| *key)
+            .collect();
+        assert_eq!(keys, [2]);
+
+        let keys: Vec<_> = cache
+            .iter_older_than(Duration::ZERO)
+            .map(|(key, _)| *key)
+            .collect();
+        assert_eq!(keys, [2, 4, 1]);
+    }
+}
@@ -0,0 +1,258 @@
+use std::collections::hash_map::RandomState;
+use std::fmt;
+
+use crate::Cache;
+
+/// A scan-resistant cache implementing the 2Q algorithm.
+///
+/// New keys enter a FIFO queue, which holds a share of the capacity set by
+/// `in_ratio`. Keys pushed out of it are remembered, without their values, in
+/// a ghost queue sized by `out_ratio`, and only a key that is inserted again
+/// while it is remembered there gets into the main LRU queue. A scan through
+/// many keys that are never used again therefore only churns the FIFO queue,
+/// leaving the entries of the main queue in place.
+///
+/// Hits in the FIFO queue don't change its order; hits in the main queue
+/// make the entry its most recently used.
+pub struct TwoQueueCache<K, V, S = RandomState> {
+    recent: Cache<K, V, S>,
+    frequent: Cache<K, V, S>,
+    ghosts: Cache<K, (), S>,
+    capacity: usize,
+    recent_capacity: usize,
+}
+
+impl<K, V> TwoQueueCache<K, V>
+where
+    K: std::cmp::Eq + std::hash::Hash + Clone,
+{
+    /// Creates a cache with the ratios suggested by the 2Q paper: a quarter of
+    /// the capacity for the FIFO queue, and a ghost queue remembering half as
+    /// many keys as the capacity.
+    pub fn new(capacity: usize) -> Self {
+        Self::with_ratios(capacity, 0.25, 0.5)
+    }
+
+    /// Creates a cache whose FIFO queue holds `in_ratio` of the capacity, and
+    /// whose ghost queue remembers `out_ratio` times the capacity keys.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `in_ratio` is not between 0 and 1, or if `out_ratio` is
+    /// negative.
+    pub fn with_ratios(capacity: usize, in_ratio: f64, out_ratio: f64) -> Self {
+        Self::with_ratios_and_hasher(capacity, in_ratio, out_ratio, RandomState::new())
+    }
+}
+
+impl<K, V, S> TwoQueueCache<K, V, S>
+where
+    K: std::cmp::Eq + std::hash::Hash + Clone,
+    S: std::hash::BuildHasher,
+{
+    /// Like [`TwoQueueCache::with_ratios`], but with the given hasher.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `in_ratio` is not between 0 and 1, or if `out_ratio` is
+    /// negative.
+    pub fn with_ratios_and_hasher(capacity: usize, in_ratio: f64, out_ratio: f64, hasher: S) -> Self
+    where
+        S: Clone,
+    {
+        assert!(
+            (0.0..=1.0).contains(&in_ratio),
+            "the ratio of the FIFO queue must be between 0 and 1"
+        );
+        assert!(
+            out_ratio >= 0.0,
+            "the ratio of the ghost queue must not be negative"
+        );
+
+        Self {
+            recent: Cache::with_hasher(usize::MAX, hasher.clone()),
+            frequent: Cache::with_hasher(usize::MAX, hasher.clone()),
+            ghosts: Cache::with_hasher((capacity as f64 * out_ratio) as usize, hasher),
+            capacity,
+            recent_capacity: (capacity as f64 * in_ratio) as usize,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.recent.len() + self.frequent.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        self.frequent.contains_key(key) || self.recent.contains_key(key)
+    }
+
+    /// Returns the value for the key, making it the most recently used entry
+    /// if it is in the main queue.
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        if self.frequent.contains_key(key) {
+            return self.frequent.get(key);
+        }
+
+        self.recent.peek(key)
+    }
+
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        self.frequent.peek(key).or_else(|| self.recent.peek(key))
+    }
+
+    /// Inserts a key-value pair, with the same return value as
+    /// [`Cache::insert`]: the passed key and the old value if the key was
+    /// present, the evicted entry if the cache was full, or the passed pair if
+    /// the cache has zero capacity.
+    ///
+    /// A new key goes into the FIFO queue, unless it is remembered by the
+    /// ghost queue, in which case it goes into the main queue. To make room,
+    /// the oldest entry of the FIFO queue is evicted while that queue is over
+    /// its share, and the least recently used entry of the main queue
+    /// otherwise.
+    pub fn insert(&mut self, key: K, value: V) -> Option<(K, V)> {
+        if self.frequent.contains_key(&key) {
+            return self.frequent.insert(key, value);
+        }
+
+        if let Some(old) = self.recent.peek_mut(&key) {
+            return Some((key, std::mem::replace(old, value)));
+        }
+
+        if self.capacity == 0 {
+            return Some((key, value));
+        }
+
+        // Look the key up before making room, which may forget it.
+        let remembered = self.ghosts.remove(&key).is_some();
+        let evicted = (self.len() >= self.capacity)
+            .then(|| self.evict())
+            .flatten();
+
+        if remembered {
+            self.frequent.insert(key, value);
+        } else {
+            self.recent.insert(key, value);
+        }
+
+        evicted
+    }
+
+    fn evict(&mut self) -> Option<(K, V)> {
+        if self.recent.len() > self.recent_capacity || self.frequent.is_empty() {
+            let (key, value) = self.recent.pop_lru()?;
+            self.ghosts.insert(key.clone(), ());
+            Some((key, value))
+        } else {
+            self.frequent.pop_lru()
+        }
+    }
+
+    /// Removes the entry for the key, and makes the ghost queue forget it.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        self.ghosts.remove(key);
+        self.frequent
+            .remove(key)
+            .or_else(|| self.recent.remove(key))
+    }
+
+    pub fn clear(&mut self) {
+        self.recent.clear();
+        self.frequent.clear();
+        self.ghosts.clear();
+    }
+}
+
+impl<K, V, S> fmt::Debug for TwoQueueCache<K, V, S>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TwoQueueCache")
+            .field("capacity", &self.capacity)
+            .field("recent", &self.recent)
+            .field("frequent", &self.frequent)
+            .field("ghosts", &self.ghosts)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_two_queue_cache() {
+        let mut cache = TwoQueueCache::new(4);
+
+        for key in 1..=4 {
+            assert_eq!(cache.insert(key, key * 10), None);
+        }
+
+        assert_eq!(cache.insert(5, 50), Some((1, 10)));
+        assert_eq!(cache.insert(1, 11), Some((2, 20)));
+        assert_eq!(cache.get(&1), Some(&11));
+        assert_eq!(cache.insert(3, 33), Some((3, 30)));
+        assert_eq!(cache.len(), 4);
+
+        assert_eq!(cache.remove(&1), Some(11));
+        assert_eq!(cache.remove(&1), None);
+        assert_eq!(cache.len(), 3);
+    }
+
+    #[test]
+    fn test_scan_resistance() {
+        let mut cache = TwoQueueCache::new(4);
+        let mut lru = Cache::new(4);
+
+        // Insert the hot keys, push them out, and bring them back.
+        for key in [1, 2, 3, 4, 5, 6, 1, 2] {
+            cache.insert(key, ());
+            lru.insert(key, ());
+        }
+
+        for key in 100..120 {
+            cache.insert(key, ());
+            lru.insert(key, ());
+            cache.get(&1);
+            lru.get(&1);
+        }
+
+        assert!(cache.contains_key(&1));
+        assert!(cache.contains_key(&2));
+        assert!(!lru.contains_key(&2));
+    }
+
+    #[test]
+    fn test_zero_capacity() {
+        let mut cache = TwoQueueCache::new(0);
+
+        assert_eq!(cache.insert(1, "aws"), Some((1, "aws")));
+        assert!(cache.is_empty());
+    }
+}
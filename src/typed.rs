@@ -0,0 +1,94 @@
+/// Defines a newtype around a [`Cache`](crate::Cache), so that caches with the
+/// same key and value types but different meanings can't be mixed up.
+///
+/// `typed_cache!(Name, K, V, capacity)` generates a `#[repr(transparent)]`
+/// tuple struct that dereferences to `Cache<K, V>`, which makes every method
+/// of the cache available on it at no cost, together with:
+///
+/// - a `DEFAULT_CAPACITY` constant holding `capacity`, used by [`Default`],
+/// - `new(capacity)` and `into_inner()`,
+/// - a [`From`] conversion from `Cache<K, V>`.
+///
+/// Attributes and a visibility may precede the name, for example to derive
+/// `Debug` or `Clone`, which the cache implements when its keys and values
+/// do.
+#[macro_export]
+macro_rules! typed_cache {
+    ($(#[$meta:meta])* $vis:vis $name:ident, $key:ty, $value:ty, $capacity:expr $(,)?) => {
+        $(#[$meta])*
+        #[repr(transparent)]
+        $vis struct $name($crate::Cache<$key, $value>);
+
+        impl $name {
+            /// The capacity of a cache created through [`Default`].
+            pub const DEFAULT_CAPACITY: usize = $capacity;
+
+            pub fn new(capacity: usize) -> Self {
+                Self($crate::Cache::new(capacity))
+            }
+
+            pub fn into_inner(self) -> $crate::Cache<$key, $value> {
+                self.0
+            }
+        }
+
+        impl ::std::default::Default for $name {
+            fn default() -> Self {
+                Self::new(Self::DEFAULT_CAPACITY)
+            }
+        }
+
+        impl ::std::convert::From<$crate::Cache<$key, $value>> for $name {
+            fn from(cache: $crate::Cache<$key, $value>) -> Self {
+                Self(cache)
+            }
+        }
+
+        impl ::std::ops::Deref for $name {
+            type Target = $crate::Cache<$key, $value>;
+
+            fn deref(&self) -> &Self::Target {
+                &self.0
+            }
+        }
+
+        impl ::std::ops::DerefMut for $name {
+            fn deref_mut(&mut self) -> &mut Self::Target {
+                &mut self.0
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Cache;
+
+    crate::typed_cache!(
+        #[derive(Debug, Clone)]
+        Regions,
+        u32,
+        &'static str,
+        2
+    );
+
+    #[test]
+    fn test_typed_cache() {
+        let mut regions = Regions::default();
+
+        assert_eq!(Regions::DEFAULT_CAPACITY, 2);
+        assert_eq!(regions.capacity(), 2);
+
+        regions.insert(1, "aws");
+        regions.insert(2, "gcp");
+        assert_eq!(regions.insert(3, "azure"), Some((1, "aws")));
+        assert_eq!(regions.clone().len(), 2);
+        assert_eq!(
+            std::mem::size_of::<Regions>(),
+            std::mem::size_of::<Cache<u32, &str>>()
+        );
+
+        let cache = regions.into_inner();
+        assert_eq!(Regions::from(cache).get(&2), Some(&"gcp"));
+    }
+}
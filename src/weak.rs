@@ -0,0 +1,170 @@
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::sync::{Arc, Weak};
+
+use crate::Cache;
+
+/// An LRU cache holding [`Weak`] references, so that it never keeps a value
+/// alive once all of its [`Arc`]s elsewhere are gone.
+///
+/// An entry whose value was dropped stays in the cache, taking up space,
+/// until it is looked up, at which point it is removed and treated as
+/// missing, or until it is evicted. [`WeakValueCache::remove_dead`] removes
+/// all of them at once. [`WeakValueCache::get_or_insert_with`] makes this an
+/// interner: as long as a value is in use, every lookup of its key returns
+/// the same `Arc`.
+pub struct WeakValueCache<K, T, S = RandomState> {
+    cache: Cache<K, Weak<T>, S>,
+}
+
+impl<K, T> WeakValueCache<K, T>
+where
+    K: std::cmp::Eq + std::hash::Hash + Clone,
+{
+    pub fn new(capacity: usize) -> Self {
+        Self::with_hasher(capacity, RandomState::new())
+    }
+}
+
+impl<K, T, S> WeakValueCache<K, T, S>
+where
+    K: std::cmp::Eq + std::hash::Hash + Clone,
+    S: std::hash::BuildHasher,
+{
+    pub fn with_hasher(capacity: usize, hasher: S) -> Self {
+        Self {
+            cache: Cache::with_hasher(capacity, hasher),
+        }
+    }
+
+    /// Returns the number of entries, including those whose value was dropped
+    /// but which have not been removed yet.
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cache.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.cache.capacity()
+    }
+
+    /// Returns the value for the key if it is still alive, making it the most
+    /// recently used entry. An entry whose value was dropped is removed.
+    pub fn get<Q>(&mut self, key: &Q) -> Option<Arc<T>>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        let value = self.cache.get(key)?.upgrade();
+
+        if value.is_none() {
+            self.cache.remove(key);
+        }
+
+        value
+    }
+
+    /// Returns the value for the key if it is still alive, or else caches the
+    /// value returned by `f`.
+    pub fn get_or_insert_with<F>(&mut self, key: K, f: F) -> Arc<T>
+    where
+        F: FnOnce() -> Arc<T>,
+    {
+        if let Some(value) = self.get(&key) {
+            return value;
+        }
+
+        let value = f();
+        self.cache.insert(key, Arc::downgrade(&value));
+        value
+    }
+
+    /// Inserts a weak reference to the value. Like [`Cache::insert`], this
+    /// returns the replaced or evicted entry, but only if its value is still
+    /// alive.
+    pub fn insert(&mut self, key: K, value: &Arc<T>) -> Option<(K, Arc<T>)> {
+        let (key, value) = self.cache.insert(key, Arc::downgrade(value))?;
+        Some((key, value.upgrade()?))
+    }
+
+    /// Removes the entry for the key, returning its value if it is still
+    /// alive.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<Arc<T>>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        self.cache.remove(key)?.upgrade()
+    }
+
+    /// Removes every entry whose value was dropped, and returns how many there
+    /// were.
+    pub fn remove_dead(&mut self) -> usize {
+        let len = self.cache.len();
+        self.cache.retain(|_, value| value.strong_count() > 0);
+        len - self.cache.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.cache.clear();
+    }
+}
+
+impl<K, T, S> fmt::Debug for WeakValueCache<K, T, S>
+where
+    K: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WeakValueCache")
+            .field("cache", &self.cache)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_weak_value_cache() {
+        let mut cache = WeakValueCache::new(2);
+        let aws = Arc::new("aws");
+
+        assert_eq!(cache.insert(1, &aws), None);
+        assert!(Arc::ptr_eq(&cache.get(&1).unwrap(), &aws));
+
+        drop(aws);
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.get(&1), None);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_interning() {
+        let mut cache = WeakValueCache::new(2);
+
+        let first = cache.get_or_insert_with(1, || Arc::new(String::from("aws")));
+        let second = cache.get_or_insert_with(1, || unreachable!());
+        assert!(Arc::ptr_eq(&first, &second));
+
+        drop((first, second));
+        let third = cache.get_or_insert_with(1, || Arc::new(String::from("gcp")));
+        assert_eq!(*third, "gcp");
+    }
+
+    #[test]
+    fn test_remove_dead() {
+        let mut cache = WeakValueCache::new(3);
+        let aws = Arc::new("aws");
+
+        cache.insert(1, &aws);
+        cache.insert(2, &Arc::new("gcp"));
+        cache.insert(3, &Arc::new("azure"));
+        assert_eq!(cache.remove_dead(), 2);
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.remove(&1), Some(aws));
+    }
+}
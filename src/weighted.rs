@@ -0,0 +1,511 @@
+use std::collections::hash_map::RandomState;
+use std::fmt;
+
+use crate::Cache;
+
+type Weigher<K, V> = Box<dyn Fn(&K, &V) -> usize + Send + Sync>;
+
+/// What a [`WeightedCache`] does with an entry that alone weighs more than
+/// its maximum.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OverweightPolicy {
+    /// Leave the cache untouched and hand the entry back.
+    #[default]
+    Reject,
+    /// Evict every other entry and keep it anyway, leaving the cache over its
+    /// maximum until the entry goes away.
+    BestEffort,
+}
+
+/// The error returned by [`WeightedCache::try_insert_weighted`], which hands
+/// the rejected pair back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WeighError<K, V> {
+    /// The pair weighs more than the maximum weight of the cache, and the
+    /// [`OverweightPolicy`] is [`OverweightPolicy::Reject`].
+    Overweight {
+        key: K,
+        value: V,
+        weight: usize,
+        max_weight: usize,
+    },
+    /// The weigher returned a weight of 0, which would let the entry stay in
+    /// the cache without ever counting towards its maximum weight.
+    InvalidWeight { key: K, value: V },
+}
+
+impl<K, V> WeighError<K, V> {
+    /// Returns the rejected pair.
+    pub fn into_pair(self) -> (K, V) {
+        match self {
+            Self::Overweight { key, value, .. } | Self::InvalidWeight { key, value } => {
+                (key, value)
+            }
+        }
+    }
+}
+
+impl<K, V> fmt::Display for WeighError<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Overweight {
+                weight, max_weight, ..
+            } => write!(
+                f,
+                "the entry weighs {weight}, more than the maximum of {max_weight}"
+            ),
+            Self::InvalidWeight { .. } => f.write_str("the weigher returned a weight of 0"),
+        }
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug> std::error::Error for WeighError<K, V> {}
+
+/// An LRU cache bounded by the total weight of its entries rather than by
+/// their number.
+///
+/// The weight of an entry is computed once, when it is inserted, by the
+/// weigher the cache was created with. A cache created with
+/// [`WeightedCache::with_limits`] also bounds the number of entries, and
+/// evicts whenever either bound is exceeded.
+pub struct WeightedCache<K, V, S = RandomState> {
+    cache: Cache<K, (V, usize), S>,
+    weigher: Weigher<K, V>,
+    max_weight: usize,
+    max_entries: usize,
+    max_evictions_per_op: usize,
+    total_weight: usize,
+    overweight_policy: OverweightPolicy,
+}
+
+impl<K, V> WeightedCache<K, V>
+where
+    K: std::cmp::Eq + std::hash::Hash + Clone,
+{
+    pub fn with_weigher<F>(max_weight: usize, weigher: F) -> Self
+    where
+        F: Fn(&K, &V) -> usize + Send + Sync + 'static,
+    {
+        Self::with_weigher_and_hasher(max_weight, weigher, RandomState::new())
+    }
+
+    /// Creates a cache holding at most `max_entries` entries that together
+    /// weigh at most `max_weight`.
+    pub fn with_limits<F>(max_entries: usize, max_weight: usize, weigher: F) -> Self
+    where
+        F: Fn(&K, &V) -> usize + Send + Sync + 'static,
+    {
+        let mut cache = Self::with_weigher(max_weight, weigher);
+        cache.max_entries = max_entries;
+        cache
+    }
+}
+
+impl<K, V, S> WeightedCache<K, V, S>
+where
+    K: std::cmp::Eq + std::hash::Hash + Clone,
+    S: std::hash::BuildHasher,
+{
+    pub fn with_weigher_and_hasher<F>(max_weight: usize, weigher: F, hasher: S) -> Self
+    where
+        F: Fn(&K, &V) -> usize + Send + Sync + 'static,
+    {
+        Self {
+            cache: Cache::with_hasher(usize::MAX, hasher),
+            weigher: Box::new(weigher),
+            max_weight,
+            max_entries: usize::MAX,
+            max_evictions_per_op: usize::MAX,
+            total_weight: 0,
+            overweight_policy: OverweightPolicy::default(),
+        }
+    }
+
+    /// Caps the number of entries a single insertion evicts, so that a heavy
+    /// entry doesn't evict many light ones at once.
+    ///
+    /// An insertion that would have to evict more leaves the cache over its
+    /// maximum weight, and only the following insertions catch up, each
+    /// evicting up to `n` least recently used entries until the cache is back
+    /// within it; lookups never evict, but removals shrink the backlog too.
+    /// Meanwhile, the cache exceeds its maximum weight by at most the total
+    /// weight of the entries inserted since it was last within it. Insertions
+    /// that add more weight than their `n` evictions take away make the
+    /// backlog grow rather than shrink. The maximum number of entries is never
+    /// exceeded, since every insertion can evict at least one entry.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is 0.
+    pub fn with_max_evictions_per_op(mut self, n: usize) -> Self {
+        assert!(
+            n > 0,
+            "an insertion must be able to evict at least one entry"
+        );
+        self.max_evictions_per_op = n;
+        self
+    }
+
+    pub fn max_evictions_per_op(&self) -> usize {
+        self.max_evictions_per_op
+    }
+
+    pub fn overweight_policy(&self) -> OverweightPolicy {
+        self.overweight_policy
+    }
+
+    pub fn set_overweight_policy(&mut self, policy: OverweightPolicy) {
+        self.overweight_policy = policy;
+    }
+
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cache.is_empty()
+    }
+
+    pub fn max_weight(&self) -> usize {
+        self.max_weight
+    }
+
+    /// Returns the maximum number of entries, which is `usize::MAX` unless
+    /// the cache was created with [`WeightedCache::with_limits`].
+    pub fn max_entries(&self) -> usize {
+        self.max_entries
+    }
+
+    pub fn total_weight(&self) -> usize {
+        self.total_weight
+    }
+
+    /// Returns how much more weight fits before an insertion evicts. This is 0
+    /// while the cache is over its maximum, be it because of an
+    /// [`OverweightPolicy::BestEffort`] entry or of a cap on evictions.
+    pub fn remaining_weight(&self) -> usize {
+        self.max_weight.saturating_sub(self.total_weight)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        self.cache.contains_key(key)
+    }
+
+    pub fn get<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        self.cache.get(key).map(|(value, _)| value)
+    }
+
+    pub fn peek<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        self.cache.peek(key).map(|(value, _)| value)
+    }
+
+    /// Inserts a key-value pair, making it the most recently used entry, and
+    /// evicts the least recently used entries until both the total weight and
+    /// the number of entries are back within their maximums.
+    ///
+    /// Returns every pair displaced by the insertion: the old value of the
+    /// key, if it was present, followed by the evicted entries in eviction
+    /// order. A pair that alone weighs more than the maximum is handled
+    /// according to the [`OverweightPolicy`]: by default it is rejected and
+    /// handed straight back, leaving the cache untouched.
+    pub fn insert(&mut self, key: K, value: V) -> Vec<(K, V)> {
+        self.try_insert(key, value)
+            .unwrap_or_else(|rejected| vec![rejected])
+    }
+
+    /// Like [`WeightedCache::insert`], but returns a pair rejected by the
+    /// [`OverweightPolicy`] as an error.
+    pub fn try_insert(&mut self, key: K, value: V) -> Result<Vec<(K, V)>, (K, V)> {
+        let weight = (self.weigher)(&key, &value);
+
+        if self.rejects(weight) {
+            return Err((key, value));
+        }
+
+        Ok(self.insert_weighed(key, value, weight))
+    }
+
+    /// Like [`WeightedCache::try_insert`], but also rejects a pair the
+    /// weigher gives a weight of 0, and explains why in the [`WeighError`].
+    pub fn try_insert_weighted(
+        &mut self,
+        key: K,
+        value: V,
+    ) -> Result<Vec<(K, V)>, WeighError<K, V>> {
+        let weight = (self.weigher)(&key, &value);
+
+        if weight == 0 {
+            return Err(WeighError::InvalidWeight { key, value });
+        }
+
+        if self.rejects(weight) {
+            return Err(WeighError::Overweight {
+                key,
+                value,
+                weight,
+                max_weight: self.max_weight,
+            });
+        }
+
+        Ok(self.insert_weighed(key, value, weight))
+    }
+
+    fn rejects(&self, weight: usize) -> bool {
+        weight > self.max_weight && self.overweight_policy == OverweightPolicy::Reject
+    }
+
+    fn insert_weighed(&mut self, key: K, value: V, weight: usize) -> Vec<(K, V)> {
+        let mut displaced = Vec::new();
+
+        if let Some((key, (value, weight))) = self.cache.insert(key, (value, weight)) {
+            self.total_weight -= weight;
+            displaced.push((key, value));
+        }
+
+        self.total_weight += weight;
+
+        // The new entry is the most recently used, so it is the last to go,
+        // and it only goes if the cache cannot hold a single entry.
+        let mut evictions = 0;
+
+        while evictions < self.max_evictions_per_op
+            && ((self.total_weight > self.max_weight && self.cache.len() > 1)
+                || self.cache.len() > self.max_entries)
+        {
+            displaced.extend(self.pop_lru());
+            evictions += 1;
+        }
+
+        displaced
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: ?Sized + std::cmp::Eq + std::hash::Hash,
+    {
+        let (value, weight) = self.cache.remove(key)?;
+        self.total_weight -= weight;
+        Some(value)
+    }
+
+    pub fn peek_lru(&self) -> Option<(&K, &V)> {
+        self.cache.peek_lru().map(|(key, (value, _))| (key, value))
+    }
+
+    pub fn pop_lru(&mut self) -> Option<(K, V)> {
+        let (key, (value, weight)) = self.cache.pop_lru()?;
+        self.total_weight -= weight;
+        Some((key, value))
+    }
+
+    pub fn clear(&mut self) {
+        self.cache.clear();
+        self.total_weight = 0;
+    }
+
+    /// Returns an iterator over the entries, from the most recently used to
+    /// the least recently used.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.cache.iter().map(|(key, (value, _))| (key, value))
+    }
+}
+
+impl<K, V, S> fmt::Debug for WeightedCache<K, V, S>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WeightedCache")
+            .field("max_weight", &self.max_weight)
+            .field("max_entries", &self.max_entries)
+            .field("total_weight", &self.total_weight)
+            .field("cache", &self.cache)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_weighted_cache() {
+        let mut cache = WeightedCache::with_weigher(12, |_, value: &String| value.len());
+
+        assert!(cache.insert(1, "aws".to_owned()).is_empty());
+        assert!(cache.insert(2, "gcp".to_owned()).is_empty());
+        assert!(cache.insert(3, "azure".to_owned()).is_empty());
+        assert_eq!(cache.total_weight(), 11);
+        assert_eq!(cache.get(&1), Some(&"aws".to_owned()));
+
+        assert_eq!(
+            cache.insert(4, "vmware".to_owned()),
+            [(2, "gcp".to_owned()), (3, "azure".to_owned())]
+        );
+        assert_eq!(cache.total_weight(), 9);
+        assert_eq!(cache.len(), 2);
+
+        assert_eq!(cache.insert(1, "a".to_owned()), [(1, "aws".to_owned())]);
+        assert_eq!(cache.total_weight(), 7);
+
+        assert_eq!(cache.remove(&4), Some("vmware".to_owned()));
+        assert_eq!(cache.total_weight(), 1);
+    }
+
+    #[test]
+    fn test_reject_overweight() {
+        let mut cache = WeightedCache::with_weigher(4, |_, value: &String| value.len());
+
+        cache.insert(1, "aws".to_owned());
+        assert_eq!(
+            cache.insert(2, "vmware".to_owned()),
+            [(2, "vmware".to_owned())]
+        );
+        assert_eq!(cache.peek(&1), Some(&"aws".to_owned()));
+        assert_eq!(cache.total_weight(), 3);
+    }
+
+    #[test]
+    fn test_try_insert() {
+        let mut cache = WeightedCache::with_weigher(4, |_, value: &String| value.len());
+
+        cache.insert(1, "aws".to_owned());
+        assert_eq!(
+            cache.try_insert(2, "vmware".to_owned()),
+            Err((2, "vmware".to_owned()))
+        );
+        assert_eq!(cache.try_insert(3, "a".to_owned()), Ok(vec![]));
+        assert_eq!(cache.total_weight(), 4);
+    }
+
+    #[test]
+    fn test_try_insert_weighted() {
+        let mut cache = WeightedCache::with_weigher(4, |_, value: &String| value.len());
+
+        let error = cache
+            .try_insert_weighted(1, "vmware".to_owned())
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "the entry weighs 6, more than the maximum of 4"
+        );
+        assert_eq!(error.into_pair(), (1, "vmware".to_owned()));
+
+        assert_eq!(
+            cache.try_insert_weighted(2, String::new()),
+            Err(WeighError::InvalidWeight {
+                key: 2,
+                value: String::new()
+            })
+        );
+        assert!(cache.is_empty());
+
+        assert_eq!(cache.try_insert_weighted(3, "aws".to_owned()), Ok(vec![]));
+        assert_eq!(cache.total_weight(), 3);
+    }
+
+    #[test]
+    fn test_overweight_best_effort() {
+        let mut cache = WeightedCache::with_weigher(4, |_, value: &String| value.len());
+        cache.set_overweight_policy(OverweightPolicy::BestEffort);
+
+        cache.insert(1, "aws".to_owned());
+        assert_eq!(
+            cache.try_insert(2, "vmware".to_owned()),
+            Ok(vec![(1, "aws".to_owned())])
+        );
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.total_weight(), 6);
+        assert_eq!(cache.remaining_weight(), 0);
+
+        assert_eq!(
+            cache.insert(3, "gcp".to_owned()),
+            [(2, "vmware".to_owned())]
+        );
+        assert_eq!(cache.total_weight(), 3);
+    }
+
+    #[test]
+    fn test_entry_limit() {
+        let mut cache = WeightedCache::with_limits(2, 100, |_, value: &String| value.len());
+
+        cache.insert(1, "aws".to_owned());
+        cache.insert(2, "gcp".to_owned());
+        assert_eq!(cache.insert(3, "azure".to_owned()), [(1, "aws".to_owned())]);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.total_weight(), 8);
+    }
+
+    #[test]
+    fn test_weight_limit_with_entry_limit() {
+        let mut cache = WeightedCache::with_limits(3, 8, |_, value: &String| value.len());
+
+        cache.insert(1, "aws".to_owned());
+        cache.insert(2, "gcp".to_owned());
+        assert_eq!(cache.insert(3, "azure".to_owned()), [(1, "aws".to_owned())]);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.total_weight(), 8);
+
+        assert_eq!(
+            cache.insert(4, "vmware".to_owned()),
+            [(2, "gcp".to_owned()), (3, "azure".to_owned())]
+        );
+        assert_eq!(cache.max_entries(), 3);
+    }
+
+    #[test]
+    fn test_max_evictions_per_op_converges() {
+        let mut cache = WeightedCache::with_weigher(10, |_, value: &String| value.len())
+            .with_max_evictions_per_op(2);
+
+        for key in 0..10 {
+            cache.insert(key, "a".to_owned());
+        }
+
+        assert_eq!(cache.insert(10, "b".repeat(8)).len(), 2);
+        assert_eq!(cache.total_weight(), 16);
+
+        // Every light insertion adds 1 and evicts at least 2 until caught up.
+        for key in 11..17 {
+            let total = cache.total_weight();
+            cache.insert(key, "c".to_owned());
+            assert!(cache.total_weight() < total || cache.total_weight() <= 10);
+        }
+
+        assert!(cache.total_weight() <= 10);
+        assert!(cache.insert(17, "d".to_owned()).len() <= 1);
+        assert!(cache.total_weight() <= 10);
+    }
+
+    #[test]
+    fn test_max_evictions_per_op() {
+        let mut cache = WeightedCache::with_weigher(4, |_, value: &String| value.len())
+            .with_max_evictions_per_op(1);
+
+        cache.insert(1, "a".to_owned());
+        cache.insert(2, "b".to_owned());
+        cache.insert(3, "c".to_owned());
+        assert_eq!(cache.insert(4, "dddd".to_owned()), [(1, "a".to_owned())]);
+        assert_eq!(cache.total_weight(), 6);
+
+        assert_eq!(cache.insert(5, "e".to_owned()), [(2, "b".to_owned())]);
+        assert_eq!(cache.insert(6, "f".to_owned()), [(3, "c".to_owned())]);
+        assert_eq!(cache.insert(7, "g".to_owned()), [(4, "dddd".to_owned())]);
+        assert_eq!(cache.total_weight(), 3);
+        assert_eq!(cache.remaining_weight(), 1);
+    }
+}
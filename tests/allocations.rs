@@ -0,0 +1,56 @@
+//! Counts the allocations made by a full cache, which needs a global
+//! allocator of its own and therefore a test binary of its own.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use shorcache::Cache;
+
+struct CountingAllocator;
+
+thread_local! {
+    // Per thread, so that the test harness doesn't disturb the count.
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|allocations| allocations.set(allocations.get() + 1));
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.with(|allocations| allocations.set(allocations.get() + 1));
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn allocations() -> usize {
+    ALLOCATIONS.with(Cell::get)
+}
+
+#[test]
+fn test_evicting_insert_does_not_allocate() {
+    let mut cache = Cache::new(64);
+
+    // Filling the cache, and the first evictions, may still grow the map, as
+    // its deleted slots pile up.
+    for key in 0..10_000_u64 {
+        cache.insert(key, key);
+    }
+
+    let before = allocations();
+
+    for key in 10_000..20_000_u64 {
+        assert_eq!(cache.insert(key, key), Some((key - 64, key - 64)));
+    }
+
+    assert_eq!(allocations() - before, 0);
+}